@@ -0,0 +1,124 @@
+//! Keeps string tables in step with the scripts.
+//!
+//! Usage: `localize extract TABLE [SCRIPTS]` or `localize check TABLE [SCRIPTS]`
+//!
+//! TABLE is a `.po` or `.csv` file, SCRIPTS defaults to
+//! `data/scripts.manifest`. `extract` writes every string of the scripts to
+//! TABLE, keeping the translations already in it and marking the ones whose
+//! source text changed as fuzzy. `check` lists strings that are untranslated,
+//! stale or gone. Both exit with 1 when two strings share an id and 2 when a
+//! file can't be loaded.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use bleeping_fyrox::{
+    localization::{check_translations, extract, StringTable},
+    manifest::{merge, script_files},
+    script::{self, Scripts},
+    validate::Severity,
+};
+
+fn usage() -> ExitCode {
+    eprintln!("usage: localize extract|check TABLE [SCRIPTS]");
+    ExitCode::from(2)
+}
+
+fn load_scripts(path: &Path) -> Result<Scripts, String> {
+    let files = script_files(path).map_err(|err| format!("couldn't read {}: {}", path.display(), err))?;
+    let mut loaded = Vec::new();
+    for file in files {
+        let scripts = script::load_from_file(&file).map_err(|err| err.to_string())?;
+        loaded.push((file, scripts));
+    }
+    let (scripts, _) =
+        merge(loaded.iter().map(|(file, scripts)| (file.as_path(), scripts))).map_err(|err| err.to_string())?;
+    Ok(scripts)
+}
+
+fn load_table(path: &Path) -> Result<StringTable, String> {
+    let data = fs::read_to_string(path).map_err(|err| format!("couldn't read {}: {}", path.display(), err))?;
+    StringTable::parse(&data, path).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (command, table_path, scripts_path) = match args.as_slice() {
+        [command, table] => (command, PathBuf::from(table), PathBuf::from("data/scripts.manifest")),
+        [command, table, scripts] => (command, PathBuf::from(table), PathBuf::from(scripts)),
+        _ => return usage(),
+    };
+    let scripts = match load_scripts(&scripts_path) {
+        Ok(scripts) => scripts,
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::from(2);
+        }
+    };
+    let previous = if table_path.exists() {
+        match load_table(&table_path) {
+            Ok(table) => Some(table),
+            Err(err) => {
+                eprintln!("{}", err);
+                return ExitCode::from(2);
+            }
+        }
+    } else {
+        None
+    };
+
+    match command.as_str() {
+        "extract" => {
+            let table = extract(&scripts, previous.as_ref());
+            if let Err(err) = fs::write(&table_path, table.write(&table_path)) {
+                eprintln!("couldn't write {}: {}", table_path.display(), err);
+                return ExitCode::from(2);
+            }
+            let translated = table.iter().filter(|(_, entry)| entry.is_translated()).count();
+            let fuzzy = table.iter().filter(|(_, entry)| entry.fuzzy).count();
+            println!(
+                "{}: {} string(s), {} translated, {} fuzzy",
+                table_path.display(),
+                table.iter().count(),
+                translated,
+                fuzzy
+            );
+        }
+        "check" if previous.is_none() => {
+            eprintln!("{} doesn't exist, run extract first", table_path.display());
+            return ExitCode::from(2);
+        }
+        "check" => {}
+        _ => return usage(),
+    }
+
+    // Strings sharing an id matter to both commands, the rest only to
+    // `check`.
+    let issues = check_translations(&scripts, &previous.unwrap_or_default());
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count();
+    if command == "check" {
+        for issue in &issues {
+            println!("{}", issue);
+        }
+        println!(
+            "{}: {} error(s), {} warning(s)",
+            table_path.display(),
+            errors,
+            issues.len() - errors
+        );
+    } else {
+        for issue in issues.iter().filter(|issue| issue.severity == Severity::Error) {
+            println!("{}", issue);
+        }
+    }
+    if errors > 0 {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,78 @@
+//! Converts a scripts file, or an Ink or Yarn file, to the screenplay format.
+//!
+//! Usage: `to-screenplay INPUT [OUTPUT]`
+//!
+//! Writes to stdout without OUTPUT. The result is parsed back and compared with
+//! what was read before anything is written, so nothing is lost in the move.
+//! Whatever an Ink or Yarn file has that couldn't be imported is listed on
+//! stderr.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use bleeping_fyrox::{
+    import::import_file,
+    location::ScriptError,
+    screenplay::{export_screenplay, parse_screenplay},
+    script::{self, Scripts},
+};
+
+fn load(input: &Path) -> Result<Scripts, ScriptError> {
+    let data = fs::read_to_string(input).map_err(|err| ScriptError::Io {
+        file: input.to_path_buf(),
+        message: err.to_string(),
+    })?;
+    match import_file(&data, input) {
+        Some(imported) => {
+            for issue in &imported.issues {
+                eprintln!("{}", issue);
+            }
+            Ok(imported.scripts)
+        }
+        None => script::parse_scripts_file(&data, input),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (input, output) = match args.as_slice() {
+        [input] => (PathBuf::from(input), None),
+        [input, output] => (PathBuf::from(input), Some(PathBuf::from(output))),
+        _ => {
+            eprintln!("usage: to-screenplay INPUT [OUTPUT]");
+            return ExitCode::from(2);
+        }
+    };
+    let scripts = match load(&input) {
+        Ok(scripts) => scripts,
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::from(2);
+        }
+    };
+    let screenplay = export_screenplay(&scripts);
+    let check_path = output.clone().unwrap_or_else(|| PathBuf::from("<stdout>"));
+    match parse_screenplay(&screenplay, &check_path) {
+        Ok(parsed) if parsed == scripts => {}
+        Ok(_) => {
+            eprintln!("the screenplay doesn't parse back into the same scripts, nothing written");
+            return ExitCode::from(1);
+        }
+        Err(err) => {
+            eprintln!("the screenplay doesn't parse back: {}", err);
+            return ExitCode::from(1);
+        }
+    }
+    match output {
+        Some(output) => {
+            if let Err(err) = fs::write(&output, screenplay) {
+                eprintln!("couldn't write {}: {}", output.display(), err);
+                return ExitCode::from(2);
+            }
+        }
+        None => print!("{}", screenplay),
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,126 @@
+//! Runs the script validator over a scripts file, a screenplay or a manifest
+//! of them.
+//!
+//! Usage: `check-scripts [PATH] [--entry SCRIPT]... [--character NAME]...`
+//!
+//! PATH defaults to `data/scripts.manifest`. Every `--entry` adds a script that
+//! doesn't need to be reachable from anything else, without one the game's
+//! start script is used. Passing `--character` switches from guessing typos to
+//! checking blurps against that list of characters.
+//! Exits with 1 when there are errors and 2 when a file can't be loaded.
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use bleeping_fyrox::{
+    location::ScriptError,
+    manifest::{merge, script_files},
+    script::{self, Scripts},
+    validate::{validate, validate_keys, Issue, Severity, ValidationOptions},
+};
+
+fn usage() -> ExitCode {
+    eprintln!("usage: check-scripts [PATH] [--entry SCRIPT]... [--character NAME]...");
+    ExitCode::from(2)
+}
+
+/// Reads and parses one file, adding its key issues to `issues`.
+fn load(path: &Path, issues: &mut Vec<Issue>) -> Result<Scripts, ScriptError> {
+    let data = fs::read_to_string(path).map_err(|err| ScriptError::Io {
+        file: path.to_path_buf(),
+        message: err.to_string(),
+    })?;
+    // Screenplays and files that aren't even JSON get their errors from
+    // `parse_scripts_file` below.
+    if let Ok(found) = validate_keys(&data, path) {
+        issues.extend(found);
+    }
+    script::parse_scripts_file(&data, path)
+}
+
+fn report(issues: &[Issue]) {
+    for issue in issues {
+        println!("{}", issue);
+    }
+}
+
+fn main() -> ExitCode {
+    let mut path = PathBuf::from("data/scripts.manifest");
+    let mut entry_points = Vec::new();
+    let mut characters = BTreeSet::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--entry" => match args.next() {
+                Some(entry) => entry_points.push(entry),
+                None => return usage(),
+            },
+            "--character" => match args.next() {
+                Some(character) => {
+                    characters.insert(character);
+                }
+                None => return usage(),
+            },
+            "-h" | "--help" => return usage(),
+            _ if arg.starts_with('-') => return usage(),
+            _ => path = PathBuf::from(arg),
+        }
+    }
+
+    let mut options = ValidationOptions::default();
+    if !entry_points.is_empty() {
+        options.entry_points = entry_points;
+    }
+    if !characters.is_empty() {
+        options.characters = Some(characters);
+    }
+
+    let files = match script_files(&path) {
+        Ok(files) => files,
+        Err(err) => {
+            eprintln!("couldn't read {}: {}", path.display(), err);
+            return ExitCode::from(2);
+        }
+    };
+    let mut issues = Vec::new();
+    let mut loaded = Vec::new();
+    for file in files {
+        match load(&file, &mut issues) {
+            Ok(scripts) => loaded.push((file, scripts)),
+            Err(err) => {
+                report(&issues);
+                eprintln!("{}", err);
+                return ExitCode::from(2);
+            }
+        }
+    }
+    let (scripts, _) = match merge(loaded.iter().map(|(file, scripts)| (file.as_path(), scripts))) {
+        Ok(merged) => merged,
+        Err(err) => {
+            report(&issues);
+            eprintln!("{}", err);
+            return ExitCode::from(1);
+        }
+    };
+    issues.extend(validate(&scripts, &options));
+
+    report(&issues);
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count();
+    println!(
+        "{}: {} error(s), {} warning(s)",
+        path.display(),
+        errors,
+        issues.len() - errors
+    );
+    if errors > 0 {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
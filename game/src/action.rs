@@ -0,0 +1,79 @@
+//! Handlers for `ScriptItem::Action`.
+//!
+//! An action's string is looked up in an `ActionRegistry`. The handler either
+//! finishes straight away or asks the runner to wait for the player, e.g.
+//! for the name prompt behind "Get Protag's name".
+use std::{collections::HashMap, fmt};
+
+use crate::expr::Variables;
+
+/// What the runner waits for after an action.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActionRequest {
+    /// Ask the player for a line of text and store it in `variable`.
+    TextInput { prompt: String, variable: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActionOutcome {
+    Continue,
+    Wait(ActionRequest),
+}
+
+pub type ActionHandler = Box<dyn Fn(&mut Variables) -> ActionOutcome>;
+
+pub struct ActionRegistry {
+    handlers: HashMap<String, ActionHandler>,
+}
+
+impl fmt::Debug for ActionRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.handlers.keys()).finish()
+    }
+}
+
+impl Default for ActionRegistry {
+    fn default() -> Self {
+        Self::with_builtin_actions()
+    }
+}
+
+impl ActionRegistry {
+    pub fn new() -> Self {
+        Self {
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Registry with the actions our scripts use.
+    pub fn with_builtin_actions() -> Self {
+        let mut registry = Self::new();
+        registry.register_text_prompt("Get Protag's name", "What's your name?", "Name");
+        registry
+    }
+
+    /// Replaces any handler already registered under `action`.
+    pub fn register(&mut self, action: &str, handler: ActionHandler) {
+        self.handlers.insert(action.to_string(), handler);
+    }
+
+    /// Registers an action that asks for text and stores it in `variable`.
+    pub fn register_text_prompt(&mut self, action: &str, prompt: &str, variable: &str) {
+        let request = ActionRequest::TextInput {
+            prompt: prompt.to_string(),
+            variable: variable.to_string(),
+        };
+        self.register(
+            action,
+            Box::new(move |_| ActionOutcome::Wait(request.clone())),
+        );
+    }
+
+    pub fn get(&self, action: &str) -> Option<&ActionHandler> {
+        self.handlers.get(action)
+    }
+
+    pub fn contains(&self, action: &str) -> bool {
+        self.handlers.contains_key(action)
+    }
+}
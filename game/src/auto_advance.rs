@@ -0,0 +1,56 @@
+//! Auto mode: lines move on by themselves after the time it takes to read
+//! them. Lines with a `wait` take that long instead, the runner counts it
+//! down while auto mode is on.
+use crate::{markup::plain_text, runner::Line};
+
+/// Reading speed auto mode assumes, in characters per second.
+pub const AUTO_CHARS_PER_SECOND: f64 = 20.0;
+/// Even a one word line stays up this long, in seconds.
+pub const AUTO_MIN_DELAY: f64 = 1.5;
+
+#[derive(Debug, Clone)]
+pub struct AutoAdvance {
+    pub enabled: bool,
+    pub chars_per_second: f64,
+    pub min_delay: f64,
+    /// Seconds the current line has been up.
+    elapsed: f64,
+}
+
+impl Default for AutoAdvance {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AutoAdvance {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            chars_per_second: AUTO_CHARS_PER_SECOND,
+            min_delay: AUTO_MIN_DELAY,
+            elapsed: 0.0,
+        }
+    }
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.reset();
+    }
+    /// Call whenever a new line comes up.
+    pub fn reset(&mut self) {
+        self.elapsed = 0.0;
+    }
+    /// How long `line` stays up in auto mode.
+    pub fn delay(&self, line: &Line) -> f64 {
+        let reading = plain_text(&line.text).chars().count() as f64 / self.chars_per_second.max(1.0);
+        reading.max(self.min_delay)
+    }
+    /// Counts `dt` seconds towards `line`, true once it's time to move on.
+    pub fn update(&mut self, line: &Line, dt: f64) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        self.elapsed += dt;
+        self.elapsed >= self.delay(line)
+    }
+}
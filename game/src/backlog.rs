@@ -0,0 +1,113 @@
+use fyrox::{
+    asset::manager::ResourceManager,
+    core::{algebra::Vector2, color::Color, pool::Handle},
+    gui::{
+        brush::Brush,
+        formatted_text::WrapMode,
+        message::MessageDirection,
+        scroll_viewer::{ScrollViewerBuilder, ScrollViewerMessage},
+        stack_panel::StackPanelBuilder,
+        text::TextBuilder,
+        widget::{WidgetBuilder, WidgetMessage},
+        BuildContext, Thickness, UiNode, UserInterface,
+    },
+};
+
+use crate::{
+    history::{History, HistoryEntry},
+    markup::plain_text,
+    nine_patch::{center_widget_builder, NinePatchBuilder},
+};
+
+const BACKLOG_WIDTH: f32 = 400.0;
+const BACKLOG_HEIGHT: f32 = 300.0;
+
+pub struct BacklogRet {
+    pub panel: Handle<UiNode>,
+    pub scroll_viewer: Handle<UiNode>,
+    /// `History::revision` the panel was built from.
+    pub revision: u64,
+}
+
+impl BacklogRet {
+    pub fn remove(&self, ui: &UserInterface) {
+        ui.send_message(WidgetMessage::remove(self.panel, MessageDirection::ToWidget));
+    }
+}
+
+/// Builds the backlog panel, oldest entry at the top. Send
+/// `scroll_to_latest` once it's built so the newest line is in view.
+pub fn build_backlog(
+    ctx: &mut BuildContext,
+    resource_manager: &ResourceManager,
+    history: &History,
+) -> BacklogRet {
+    let mut wb = WidgetBuilder::new();
+    for entry in history.iter() {
+        wb = wb.with_child(backlog_line(ctx, entry));
+    }
+    if history.is_empty() {
+        wb = wb.with_child(
+            TextBuilder::new(WidgetBuilder::new())
+                .with_text("Nothing has been said yet.")
+                .build(ctx),
+        );
+    }
+    let list = StackPanelBuilder::new(wb).build(ctx);
+    let scroll_viewer = ScrollViewerBuilder::new(
+        center_widget_builder()
+            .with_width(BACKLOG_WIDTH)
+            .with_height(BACKLOG_HEIGHT),
+    )
+    .with_content(list)
+    .with_horizontal_scroll_allowed(false)
+    .build(ctx);
+    let panel = NinePatchBuilder::new(
+        resource_manager,
+        "data/9boxblur.png",
+        40,
+        41,
+        40,
+        41,
+        81,
+        81,
+    )
+    .with_center(scroll_viewer)
+    .build(ctx);
+    BacklogRet {
+        panel,
+        scroll_viewer,
+        revision: history.revision(),
+    }
+}
+
+pub fn scroll_to_latest(ui: &UserInterface, backlog: &BacklogRet) {
+    ui.send_message(ScrollViewerMessage::scroll_to_end(
+        backlog.scroll_viewer,
+        MessageDirection::ToWidget,
+    ));
+}
+
+/// Lines read "Character: text", the player's picks are set apart in a
+/// different colour.
+fn backlog_line(ctx: &mut BuildContext, entry: &HistoryEntry) -> Handle<UiNode> {
+    let (text, wb) = match entry {
+        HistoryEntry::Line { character, text } if character.is_empty() => {
+            (plain_text(text), WidgetBuilder::new())
+        }
+        HistoryEntry::Line { character, text } => {
+            (format!("{}: {}", character, plain_text(text)), WidgetBuilder::new())
+        }
+        HistoryEntry::Choice(text) => (
+            format!("> {}", plain_text(text)),
+            WidgetBuilder::new().with_foreground(Brush::Solid(Color::opaque(255, 220, 120))),
+        ),
+    };
+    TextBuilder::new(
+        wb.with_max_size(Vector2::new(BACKLOG_WIDTH - 20.0, f32::INFINITY))
+            .with_margin(Thickness::bottom(4.0)),
+    )
+    .with_wrap(WrapMode::Word)
+    .with_text(text)
+    .build(ctx)
+}
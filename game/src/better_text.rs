@@ -1,52 +1,277 @@
-use crate::{
-    brush::Brush,
-    core::{algebra::Vector2, color::Color, pool::Handle},
-    define_constructor,
-    draw::DrawingContext,
-    formatted_text::{FormattedText, FormattedTextBuilder, WrapMode},
-    message::{MessageDirection, UiMessage},
-    ttf::SharedFont,
-    widget::{Widget, WidgetBuilder},
-    BuildContext, Control, HorizontalAlignment, UiNode, UserInterface, VerticalAlignment,
+//! Text widget for dialogue: styled and animated runs from `markup`, revealed
+//! a few characters at a time.
+//!
+//! It started out as a copy of Fyrox's `Text`, but lays the text out itself
+//! since `FormattedText` only knows one font and size. With a
+//! `chars_per_second` of 0 everything is shown at once. Icons need a
+//! resource manager to load them, without one they're left blank.
+use fyrox::{
+    asset::manager::ResourceManager,
+    core::log::Log,
+    gui::{
+        brush::Brush,
+        core::{algebra::Vector2, color::Color, math::Rect, pool::Handle},
+        define_constructor,
+        draw::{CommandTexture, Draw, DrawingContext, SharedTexture},
+        formatted_text::WrapMode,
+        message::{MessageDirection, UiMessage},
+        ttf::SharedFont,
+        widget::{Widget, WidgetBuilder},
+        BuildContext, Control, HorizontalAlignment, UiNode, UserInterface, VerticalAlignment,
+    },
+    resource::texture::Texture,
+    utils::into_gui_texture,
 };
 use std::{
     any::{Any, TypeId},
     cell::RefCell,
     ops::{Deref, DerefMut},
+    sync::mpsc::Sender,
 };
 
+use crate::markup::{parse_markup, Markup, Pause, Style, ICON_CHAR};
+
+/// Reveal speed of the dialogue box.
+pub const DIALOGUE_CHARS_PER_SECOND: f32 = 40.0;
+/// How far the top of a glyph leans right, as a share of its height, when
+/// italics have to be faked.
+const FAKE_ITALIC_SLANT: f32 = 0.2;
+/// Directions the outline is drawn out in, one copy of the text each.
+const OUTLINE_DIRECTIONS: [(f32, f32); 8] = [
+    (-1.0, -1.0),
+    (0.0, -1.0),
+    (1.0, -1.0),
+    (-1.0, 0.0),
+    (1.0, 0.0),
+    (-1.0, 1.0),
+    (0.0, 1.0),
+    (1.0, 1.0),
+];
+/// How often `<shake>` glyphs jump to a new spot, per second.
+const SHAKE_RATE: f32 = 24.0;
+/// Radians per second the `<wave>` moves along.
+const WAVE_SPEED: f32 = 6.0;
+/// Radians between neighbouring glyphs of a `<wave>`.
+const WAVE_SPACING: f32 = 0.6;
+/// Trips round the colour wheel per second for `<rainbow>`.
+const RAINBOW_SPEED: f32 = 0.5;
+/// Share of the colour wheel between neighbouring glyphs of a `<rainbow>`.
+const RAINBOW_SPACING: f32 = 0.07;
+/// Seconds a `<fade>` glyph takes to fade in.
+const FADE_SECONDS: f32 = 0.35;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TextMessage {
     Text(String),
     Wrap(WrapMode),
     Font(SharedFont),
+    BoldFont(SharedFont),
+    ItalicFont(SharedFont),
+    BoldItalicFont(SharedFont),
+    /// In pixels, `<size>` tags are relative to it.
+    FontSize(f32),
     VerticalAlignment(VerticalAlignment),
     HorizontalAlignment(HorizontalAlignment),
     Shadow(bool),
     ShadowDilation(f32),
     ShadowBrush(Brush),
     ShadowOffset(Vector2<f32>),
+    Outline(bool),
+    OutlineBrush(Brush),
+    /// In pixels.
+    OutlineThickness(f32),
+    /// Brush of `<em>` text that has no colour of its own.
+    EmphasisBrush(Brush),
+    /// 0 turns the reveal off, the current text is shown in full.
+    CharsPerSecond(f32),
+    /// Skips to the end of the reveal, e.g. when the player clicks.
+    RevealAll,
 }
 
 impl TextMessage {
     define_constructor!(TextMessage:Text => fn text(String), layout: false);
     define_constructor!(TextMessage:Wrap=> fn wrap(WrapMode), layout: false);
     define_constructor!(TextMessage:Font => fn font(SharedFont), layout: false);
+    define_constructor!(TextMessage:BoldFont => fn bold_font(SharedFont), layout: false);
+    define_constructor!(TextMessage:ItalicFont => fn italic_font(SharedFont), layout: false);
+    define_constructor!(TextMessage:BoldItalicFont => fn bold_italic_font(SharedFont), layout: false);
+    define_constructor!(TextMessage:FontSize => fn font_size(f32), layout: false);
     define_constructor!(TextMessage:VerticalAlignment => fn vertical_alignment(VerticalAlignment), layout: false);
     define_constructor!(TextMessage:HorizontalAlignment => fn horizontal_alignment(HorizontalAlignment), layout: false);
     define_constructor!(TextMessage:Shadow => fn shadow(bool), layout: false);
     define_constructor!(TextMessage:ShadowDilation => fn shadow_dilation(f32), layout: false);
     define_constructor!(TextMessage:ShadowBrush => fn shadow_brush(Brush), layout: false);
     define_constructor!(TextMessage:ShadowOffset => fn shadow_offset(Vector2<f32>), layout: false);
+    define_constructor!(TextMessage:Outline => fn outline(bool), layout: false);
+    define_constructor!(TextMessage:OutlineBrush => fn outline_brush(Brush), layout: false);
+    define_constructor!(TextMessage:OutlineThickness => fn outline_thickness(f32), layout: false);
+    define_constructor!(TextMessage:EmphasisBrush => fn emphasis_brush(Brush), layout: false);
+    define_constructor!(TextMessage:CharsPerSecond => fn chars_per_second(f32), layout: false);
+    define_constructor!(TextMessage:RevealAll => fn reveal_all(), layout: false);
+}
+
+/// Fonts for the styles in `markup`. Bold and italics are faked from the
+/// regular font when there's no font for them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fonts {
+    pub regular: SharedFont,
+    pub bold: Option<SharedFont>,
+    pub italic: Option<SharedFont>,
+    pub bold_italic: Option<SharedFont>,
+}
+
+impl Fonts {
+    pub fn new(regular: SharedFont) -> Self {
+        Self {
+            regular,
+            bold: None,
+            italic: None,
+            bold_italic: None,
+        }
+    }
+
+    /// The font for `style` and what's left to fake: bold, italic.
+    fn pick(&self, style: &Style) -> (&SharedFont, bool, bool) {
+        match (style.bold, style.italic) {
+            (true, true) => match (&self.bold_italic, &self.bold, &self.italic) {
+                (Some(font), _, _) => (font, false, false),
+                (None, Some(font), _) => (font, false, true),
+                (None, None, Some(font)) => (font, true, false),
+                (None, None, None) => (&self.regular, true, true),
+            },
+            (true, false) => self.bold.as_ref().map_or((&self.regular, true, false), |font| (font, false, false)),
+            (false, true) => self.italic.as_ref().map_or((&self.regular, false, true), |font| (font, false, false)),
+            (false, false) => (&self.regular, false, false),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Reveal {
+    chars_per_second: f32,
+    /// Characters shown so far, the fraction is the next one on its way.
+    shown: f32,
+    total: usize,
+    /// Pauses not reached yet, in order.
+    pauses: Vec<Pause>,
+    /// Seconds left of the pause the reveal is held at.
+    pause_left: f32,
+}
+
+impl Reveal {
+    fn start(&mut self, total: usize, mut pauses: Vec<Pause>) {
+        pauses.reverse();
+        self.total = total;
+        self.pauses = pauses;
+        self.pause_left = 0.0;
+        self.shown = if self.chars_per_second > 0.0 { 0.0 } else { total as f32 };
+    }
+    fn finish(&mut self) {
+        self.shown = self.total as f32;
+        self.pauses.clear();
+        self.pause_left = 0.0;
+    }
+    fn is_running(&self) -> bool {
+        (self.shown as usize) < self.total
+    }
+    fn update(&mut self, mut dt: f32) {
+        while dt > 0.0 && self.is_running() {
+            if self.pause_left > 0.0 {
+                let waited = self.pause_left.min(dt);
+                self.pause_left -= waited;
+                dt -= waited;
+                continue;
+            }
+            let mut shown = self.shown + dt * self.chars_per_second;
+            dt = 0.0;
+            if let Some(pause) = self.pauses.last() {
+                if shown >= pause.at as f32 {
+                    // Whatever time is left over is spent in the pause.
+                    dt = (shown - pause.at as f32) / self.chars_per_second;
+                    shown = pause.at as f32;
+                    self.pause_left = pause.seconds;
+                    self.pauses.pop();
+                }
+            }
+            self.shown = shown.min(self.total as f32);
+        }
+    }
+}
+
+/// What a glyph is drawn from.
+#[derive(Clone, PartialEq)]
+enum GlyphTexture {
+    Font(SharedFont),
+    /// Index into `Text::icon_textures`.
+    Icon(usize),
+}
+
+/// A glyph or icon of the laid out text, relative to the widget.
+#[derive(Clone)]
+struct PlacedGlyph {
+    /// Character of the text without tags this glyph shows.
+    index: usize,
+    texture: GlyphTexture,
+    bounds: Rect<f32>,
+    tex_coords: [Vector2<f32>; 4],
+    style: Style,
+    /// Drawn twice, a pixel apart, for want of a bold font.
+    fake_bold: bool,
+    /// Top edge pushed right by this share of the height, for want of an
+    /// italic font.
+    slant: f32,
+}
+
+/// Where a character sits on its line, before alignment.
+struct Placement {
+    font: SharedFont,
+    /// Font pixels to screen pixels, for `<size>`.
+    scale: f32,
+    advance: f32,
+    ascender: f32,
+    descender: f32,
+    fake_bold: bool,
+    fake_italic: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Line {
+    begin: usize,
+    end: usize,
+    width: f32,
 }
 
 #[derive(Clone)]
 pub struct Text {
     pub widget: Widget,
-    pub formatted_text: RefCell<FormattedText>,
+    markup: Markup,
+    fonts: Fonts,
+    /// In pixels, the regular font's own height when `None`.
+    font_size: Option<f32>,
+    wrap: WrapMode,
+    vertical_alignment: VerticalAlignment,
+    horizontal_alignment: HorizontalAlignment,
+    shadow: bool,
+    shadow_brush: Brush,
+    shadow_dilation: f32,
+    shadow_offset: Vector2<f32>,
+    outline: bool,
+    outline_brush: Brush,
+    outline_thickness: f32,
+    emphasis_brush: Brush,
+    resource_manager: Option<ResourceManager>,
+    /// One for each of `markup.icons`, `None` when it couldn't be requested.
+    icon_textures: Vec<Option<SharedTexture>>,
+    /// Rebuilt by every measure.
+    glyphs: RefCell<Vec<PlacedGlyph>>,
+    reveal: Reveal,
+    /// Seconds since the widget was built, drives the effects.
+    time: f32,
+    /// When each shown character of the text came up, by `time`.
+    appeared: Vec<f32>,
 }
 
-crate::define_widget_deref!(Text);
+fyrox::gui::define_widget_deref!(Text);
 
 impl Control for Text {
     fn query_component(&self, type_id: TypeId) -> Option<&dyn Any> {
@@ -58,20 +283,39 @@ impl Control for Text {
     }
 
     fn measure_override(&self, _: &UserInterface, available_size: Vector2<f32>) -> Vector2<f32> {
-        self.formatted_text
-            .borrow_mut()
-            .set_constraint(available_size)
-            .set_brush(self.widget.foreground())
-            .build()
+        let (glyphs, size) = self.layout(available_size);
+        *self.glyphs.borrow_mut() = glyphs;
+        size
     }
 
     fn draw(&self, drawing_context: &mut DrawingContext) {
-        let bounds = self.widget.bounding_rect();
-        drawing_context.draw_text(
-            self.clip_bounds(),
-            bounds.position,
-            &self.formatted_text.borrow(),
-        );
+        let position = self.widget.bounding_rect().position;
+        let shown = self.reveal.shown as usize;
+        if self.shadow {
+            self.draw_glyphs(
+                drawing_context,
+                position + self.shadow_offset,
+                shown,
+                self.shadow_dilation,
+                Some(&self.shadow_brush),
+            );
+        }
+        if self.outline {
+            for (x, y) in OUTLINE_DIRECTIONS {
+                let offset = Vector2::new(x, y) * self.outline_thickness;
+                self.draw_glyphs(drawing_context, position + offset, shown, 0.0, Some(&self.outline_brush));
+            }
+        }
+        self.draw_glyphs(drawing_context, position, shown, 0.0, None);
+    }
+
+    fn update(&mut self, dt: f32, _sender: &Sender<UiMessage>) {
+        self.time += dt;
+        self.reveal.update(dt);
+        let shown = self.reveal.shown as usize;
+        while self.appeared.len() < shown {
+            self.appeared.push(self.time);
+        }
     }
 
     fn handle_routed_message(&mut self, ui: &mut UserInterface, message: &mut UiMessage) {
@@ -79,69 +323,74 @@ impl Control for Text {
 
         if message.destination() == self.handle() {
             if let Some(msg) = message.data::<TextMessage>() {
-                let mut text_ref = self.formatted_text.borrow_mut();
                 match msg {
+                    // Always starts the reveal over, even for the text that's
+                    // already there.
                     TextMessage::Text(text) => {
-                        text_ref.set_text(text);
-                        drop(text_ref);
+                        self.markup = parse_markup(text);
+                        self.reveal
+                            .start(self.markup.text.chars().count(), self.markup.pauses.clone());
+                        self.appeared.clear();
+                        self.icon_textures = request_icons(self.resource_manager.as_ref(), &self.markup);
                         self.invalidate_layout();
                     }
                     &TextMessage::Wrap(wrap) => {
-                        if text_ref.wrap_mode() != wrap {
-                            text_ref.set_wrap(wrap);
-                            drop(text_ref);
+                        if self.wrap != wrap {
+                            self.wrap = wrap;
                             self.invalidate_layout();
                         }
                     }
                     TextMessage::Font(font) => {
-                        if &text_ref.get_font() != font {
-                            text_ref.set_font(font.clone());
-                            drop(text_ref);
+                        if &self.fonts.regular != font {
+                            self.fonts.regular = font.clone();
                             self.invalidate_layout();
                         }
                     }
-                    &TextMessage::HorizontalAlignment(horizontal_alignment) => {
-                        if text_ref.horizontal_alignment() != horizontal_alignment {
-                            text_ref.set_horizontal_alignment(horizontal_alignment);
-                            drop(text_ref);
-                            self.invalidate_layout();
-                        }
+                    TextMessage::BoldFont(font) => {
+                        self.fonts.bold = Some(font.clone());
+                        self.invalidate_layout();
                     }
-                    &TextMessage::VerticalAlignment(vertical_alignment) => {
-                        if text_ref.vertical_alignment() != vertical_alignment {
-                            text_ref.set_vertical_alignment(vertical_alignment);
-                            drop(text_ref);
-                            self.invalidate_layout();
-                        }
+                    TextMessage::ItalicFont(font) => {
+                        self.fonts.italic = Some(font.clone());
+                        self.invalidate_layout();
                     }
-                    &TextMessage::Shadow(shadow) => {
-                        if text_ref.shadow != shadow {
-                            text_ref.set_shadow(shadow);
-                            drop(text_ref);
+                    TextMessage::BoldItalicFont(font) => {
+                        self.fonts.bold_italic = Some(font.clone());
+                        self.invalidate_layout();
+                    }
+                    &TextMessage::FontSize(size) => {
+                        if self.font_size != Some(size) {
+                            self.font_size = Some(size);
                             self.invalidate_layout();
                         }
                     }
-                    TextMessage::ShadowBrush(brush) => {
-                        if &text_ref.shadow_brush != brush {
-                            text_ref.set_shadow_brush(brush.clone());
-                            drop(text_ref);
+                    &TextMessage::HorizontalAlignment(horizontal_alignment) => {
+                        if self.horizontal_alignment != horizontal_alignment {
+                            self.horizontal_alignment = horizontal_alignment;
                             self.invalidate_layout();
                         }
                     }
-                    &TextMessage::ShadowDilation(dilation) => {
-                        if text_ref.shadow_dilation != dilation {
-                            text_ref.set_shadow_dilation(dilation);
-                            drop(text_ref);
+                    &TextMessage::VerticalAlignment(vertical_alignment) => {
+                        if self.vertical_alignment != vertical_alignment {
+                            self.vertical_alignment = vertical_alignment;
                             self.invalidate_layout();
                         }
                     }
-                    &TextMessage::ShadowOffset(offset) => {
-                        if text_ref.shadow_offset != offset {
-                            text_ref.set_shadow_offset(offset);
-                            drop(text_ref);
-                            self.invalidate_layout();
+                    &TextMessage::Shadow(shadow) => self.shadow = shadow,
+                    TextMessage::ShadowBrush(brush) => self.shadow_brush = brush.clone(),
+                    &TextMessage::ShadowDilation(dilation) => self.shadow_dilation = dilation,
+                    &TextMessage::ShadowOffset(offset) => self.shadow_offset = offset,
+                    &TextMessage::Outline(outline) => self.outline = outline,
+                    TextMessage::OutlineBrush(brush) => self.outline_brush = brush.clone(),
+                    &TextMessage::OutlineThickness(thickness) => self.outline_thickness = thickness,
+                    TextMessage::EmphasisBrush(brush) => self.emphasis_brush = brush.clone(),
+                    &TextMessage::CharsPerSecond(chars_per_second) => {
+                        self.reveal.chars_per_second = chars_per_second.max(0.0);
+                        if chars_per_second <= 0.0 {
+                            self.reveal.finish();
                         }
                     }
+                    TextMessage::RevealAll => self.reveal.finish(),
                 }
             }
         }
@@ -150,30 +399,414 @@ impl Control for Text {
 
 impl Text {
     pub fn wrap_mode(&self) -> WrapMode {
-        self.formatted_text.borrow().wrap_mode()
+        self.wrap
     }
 
+    /// The text as it reads, without tags.
     pub fn text(&self) -> String {
-        self.formatted_text.borrow().text()
+        self.markup.text.clone()
     }
 
     pub fn font(&self) -> SharedFont {
-        self.formatted_text.borrow().get_font()
+        self.fonts.regular.clone()
     }
 
     pub fn vertical_alignment(&self) -> VerticalAlignment {
-        self.formatted_text.borrow().vertical_alignment()
+        self.vertical_alignment
     }
 
     pub fn horizontal_alignment(&self) -> HorizontalAlignment {
-        self.formatted_text.borrow().horizontal_alignment()
+        self.horizontal_alignment
+    }
+
+    /// Whether some of the text is still to come.
+    pub fn is_revealing(&self) -> bool {
+        self.reveal.is_running()
+    }
+
+    fn placements(&self) -> Vec<Placement> {
+        let base_size = self
+            .font_size
+            .unwrap_or_else(|| self.fonts.regular.0.lock().height());
+        let chars: Vec<char> = self.markup.text.chars().collect();
+        let mut placements = Vec::with_capacity(chars.len());
+        for run in &self.markup.runs {
+            let (font, fake_bold, fake_italic) = self.fonts.pick(&run.style);
+            let font_ref = font.0.lock();
+            let size = run.style.size.unwrap_or(base_size) * run.style.scale;
+            let scale = size / font_ref.height().max(1.0);
+            for &character in &chars[run.start..run.end] {
+                let advance = if character == ICON_CHAR {
+                    icon_size(font_ref.ascender(), scale) / scale
+                } else {
+                    font_ref
+                        .glyph(character as u32)
+                        .map_or(font_ref.height(), |glyph| glyph.advance)
+                };
+                placements.push(Placement {
+                    font: font.clone(),
+                    scale,
+                    advance: advance * scale + if fake_bold { 1.0 } else { 0.0 },
+                    ascender: font_ref.ascender() * scale,
+                    descender: font_ref.descender() * scale,
+                    fake_bold,
+                    fake_italic,
+                });
+            }
+        }
+        placements
+    }
+
+    fn layout(&self, constraint: Vector2<f32>) -> (Vec<PlacedGlyph>, Vector2<f32>) {
+        let chars: Vec<char> = self.markup.text.chars().collect();
+        let placements = self.placements();
+        let advances: Vec<f32> = placements.iter().map(|placement| placement.advance).collect();
+        let lines = break_lines(&chars, &advances, self.wrap, constraint.x);
+
+        let (empty_ascender, empty_descender) = {
+            let font = self.fonts.regular.0.lock();
+            let scale = self.font_size.map_or(1.0, |size| size / font.height().max(1.0));
+            (font.ascender() * scale, font.descender() * scale)
+        };
+        // Ascender and descender of every line, the tallest glyph decides.
+        let metrics: Vec<(f32, f32)> = lines
+            .iter()
+            .map(|line| {
+                let placements = &placements[line.begin..line.end];
+                if placements.is_empty() {
+                    return (empty_ascender, empty_descender);
+                }
+                placements.iter().fold((0.0f32, 0.0f32), |(ascender, descender), placement| {
+                    (ascender.max(placement.ascender), descender.min(placement.descender))
+                })
+            })
+            .collect();
+        // Minus here is because descender has negative value.
+        let total_height: f32 = metrics.iter().map(|(ascender, descender)| ascender - descender).sum();
+        let width = lines.iter().map(|line| line.width).fold(0.0, f32::max);
+
+        let mut y = match self.vertical_alignment {
+            VerticalAlignment::Center if constraint.y.is_finite() => {
+                (constraint.y - total_height).max(0.0) * 0.5
+            }
+            VerticalAlignment::Bottom if constraint.y.is_finite() => (constraint.y - total_height).max(0.0),
+            _ => 0.0,
+        };
+        let mut glyphs = Vec::new();
+        for (line, (ascender, descender)) in lines.iter().zip(metrics) {
+            let mut x = match self.horizontal_alignment {
+                HorizontalAlignment::Center if constraint.x.is_finite() => {
+                    (constraint.x - line.width).max(0.0) * 0.5
+                }
+                HorizontalAlignment::Right if constraint.x.is_finite() => (constraint.x - line.width).max(0.0),
+                _ => 0.0,
+            };
+            let baseline = y + ascender;
+            for index in line.begin..line.end {
+                let placement = &placements[index];
+                let font = placement.font.0.lock();
+                if chars[index] == ICON_CHAR {
+                    // Sits on the baseline, as tall as the capitals around it.
+                    let size = icon_size(font.ascender(), placement.scale);
+                    if let Some(icon) = self.markup.icons.iter().position(|icon| icon.at == index) {
+                        glyphs.push(PlacedGlyph {
+                            index,
+                            texture: GlyphTexture::Icon(icon),
+                            bounds: Rect::new(x, baseline - size, size, size),
+                            tex_coords: [
+                                Vector2::new(0.0, 0.0),
+                                Vector2::new(1.0, 0.0),
+                                Vector2::new(1.0, 1.0),
+                                Vector2::new(0.0, 1.0),
+                            ],
+                            style: self.markup.style_at(index),
+                            fake_bold: false,
+                            slant: 0.0,
+                        });
+                    }
+                } else if let Some(glyph) = font.glyph(chars[index] as u32) {
+                    let scale = placement.scale;
+                    glyphs.push(PlacedGlyph {
+                        index,
+                        texture: GlyphTexture::Font(placement.font.clone()),
+                        bounds: Rect::new(
+                            x + (glyph.left * scale).floor(),
+                            baseline - ((glyph.top + glyph.bitmap_height as f32) * scale).floor(),
+                            glyph.bitmap_width as f32 * scale,
+                            glyph.bitmap_height as f32 * scale,
+                        ),
+                        tex_coords: glyph.tex_coords,
+                        style: self.markup.style_at(index),
+                        fake_bold: placement.fake_bold,
+                        slant: if placement.fake_italic { FAKE_ITALIC_SLANT } else { 0.0 },
+                    });
+                }
+                x += placement.advance;
+            }
+            y += ascender - descender;
+        }
+        (glyphs, Vector2::new(width, total_height))
+    }
+
+    /// Brush a glyph is drawn with when there's no shadow brush for it.
+    fn brush_of(&self, style: &Style) -> Brush {
+        match style.color {
+            Some([r, g, b, a]) => Brush::Solid(Color::from_rgba(r, g, b, a)),
+            None if style.emphasis => self.emphasis_brush.clone(),
+            None => self.widget.foreground(),
+        }
+    }
+
+    /// Where `<shake>` and `<wave>` have moved `glyph` to right now.
+    fn effect_offset(&self, glyph: &PlacedGlyph) -> Vector2<f32> {
+        let mut offset = Vector2::new(0.0, 0.0);
+        if let Some(pixels) = glyph.style.shake {
+            let step = (self.time * SHAKE_RATE) as u32;
+            offset.x += jitter(glyph.index as u32, step, 0) * pixels;
+            offset.y += jitter(glyph.index as u32, step, 1) * pixels;
+        }
+        if let Some(pixels) = glyph.style.wave {
+            offset.y += (self.time * WAVE_SPEED - glyph.index as f32 * WAVE_SPACING).sin() * pixels;
+        }
+        offset
+    }
+
+    /// 0 to 1, how far a `<fade>` glyph has faded in. The rest are always 1.
+    fn effect_alpha(&self, glyph: &PlacedGlyph) -> f32 {
+        if !glyph.style.fade {
+            return 1.0;
+        }
+        match self.appeared.get(glyph.index) {
+            Some(appeared) => ((self.time - appeared) / FADE_SECONDS).clamp(0.0, 1.0),
+            // Shown, but not by an update yet.
+            None => 0.0,
+        }
+    }
+
+    /// Draws the first `shown` characters, batched by font and brush. Glyphs
+    /// with effects get their own brush and position every frame.
+    fn draw_glyphs(
+        &self,
+        drawing_context: &mut DrawingContext,
+        position: Vector2<f32>,
+        shown: usize,
+        dilation: f32,
+        brush: Option<&Brush>,
+    ) {
+        let clip_bounds = self.clip_bounds();
+        let glyphs = self.glyphs.borrow();
+        let mut batch: Option<(GlyphTexture, Brush)> = None;
+        for glyph in glyphs.iter().take_while(|glyph| glyph.index < shown) {
+            let texture = match &glyph.texture {
+                GlyphTexture::Icon(icon) if self.icon_textures[*icon].is_none() => continue,
+                texture => texture,
+            };
+            let mut glyph_brush = match brush {
+                Some(brush) => brush.clone(),
+                // Icons keep their own colours.
+                None if matches!(texture, GlyphTexture::Icon(_)) => Brush::Solid(Color::WHITE),
+                None if glyph.style.rainbow => {
+                    let hue = self.time * RAINBOW_SPEED + glyph.index as f32 * RAINBOW_SPACING;
+                    let alpha = glyph.style.color.map_or(255, |[_, _, _, a]| a);
+                    Brush::Solid(rainbow(hue, alpha))
+                }
+                None => self.brush_of(&glyph.style),
+            };
+            let alpha = self.effect_alpha(glyph);
+            if alpha <= 0.0 {
+                continue;
+            }
+            if alpha < 1.0 {
+                glyph_brush = faded(glyph_brush, alpha);
+            }
+            let same_batch = batch
+                .as_ref()
+                .is_some_and(|(batch_texture, brush)| batch_texture == texture && *brush == glyph_brush);
+            if !same_batch {
+                if let Some((texture, brush)) = batch.take() {
+                    drawing_context.commit(clip_bounds, brush, self.command_texture(texture), None);
+                }
+                batch = Some((texture.clone(), glyph_brush));
+            }
+            let bounds = glyph
+                .bounds
+                .translate(position + self.effect_offset(glyph))
+                .inflate(dilation, dilation);
+            push_glyph(drawing_context, &bounds, &glyph.tex_coords, glyph.slant);
+            if glyph.fake_bold {
+                let bounds = bounds.translate(Vector2::new(1.0, 0.0));
+                push_glyph(drawing_context, &bounds, &glyph.tex_coords, glyph.slant);
+            }
+        }
+        if let Some((texture, brush)) = batch {
+            drawing_context.commit(clip_bounds, brush, self.command_texture(texture), None);
+        }
+    }
+
+    fn command_texture(&self, texture: GlyphTexture) -> CommandTexture {
+        match texture {
+            GlyphTexture::Font(font) => CommandTexture::Font(font),
+            GlyphTexture::Icon(icon) => self.icon_textures[icon]
+                .clone()
+                .map_or(CommandTexture::None, CommandTexture::Texture),
+        }
     }
 }
 
+/// Breaks the text into lines no wider than `constraint`, a word at a time
+/// or a letter at a time depending on `wrap`. Words can span styled runs.
+fn break_lines(chars: &[char], advances: &[f32], wrap: WrapMode, constraint: f32) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut line = Line {
+        begin: 0,
+        end: 0,
+        width: 0.0,
+    };
+    // Where the line can be broken without splitting a word, and how wide
+    // the line is up to there.
+    let mut last_break: Option<(usize, f32)> = None;
+    for (index, (&character, &advance)) in chars.iter().zip(advances).enumerate() {
+        if character == '\n' {
+            line.end = index;
+            lines.push(line);
+            line = Line {
+                begin: index + 1,
+                end: index + 1,
+                width: 0.0,
+            };
+            last_break = None;
+            continue;
+        }
+        let overflows = line.width + advance > constraint && index > line.begin;
+        if overflows && !character.is_whitespace() {
+            match (wrap, last_break) {
+                (WrapMode::NoWrap, _) => {}
+                (WrapMode::Word, Some((at, width))) => {
+                    lines.push(Line {
+                        begin: line.begin,
+                        end: at,
+                        width,
+                    });
+                    line.begin = at;
+                    line.width = advances[at..index].iter().sum();
+                    last_break = None;
+                }
+                _ => {
+                    line.end = index;
+                    lines.push(line);
+                    line = Line {
+                        begin: index,
+                        end: index,
+                        width: 0.0,
+                    };
+                    last_break = None;
+                }
+            }
+        }
+        line.width += advance;
+        line.end = index + 1;
+        if character.is_whitespace() {
+            last_break = Some((index + 1, line.width - advance));
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+/// Side of the square an icon is drawn in, for a font with `ascender` drawn
+/// at `scale`.
+fn icon_size(ascender: f32, scale: f32) -> f32 {
+    (ascender * scale).round()
+}
+
+fn request_icons(resource_manager: Option<&ResourceManager>, markup: &Markup) -> Vec<Option<SharedTexture>> {
+    markup
+        .icons
+        .iter()
+        .map(|icon| match resource_manager {
+            Some(resource_manager) => Some(into_gui_texture(
+                resource_manager.request::<Texture, _>(&icon.path),
+            )),
+            None => {
+                Log::warn(format!("no resource manager to load icon {}", icon.path));
+                None
+            }
+        })
+        .collect()
+}
+
+/// -1 to 1, the same for the same glyph, step and axis. Cheap enough to call
+/// per glyph per frame and the same on every platform, unlike a seeded rng.
+fn jitter(index: u32, step: u32, axis: u32) -> f32 {
+    let mut hash = index
+        .wrapping_mul(0x9E37_79B9)
+        ^ step.wrapping_mul(0x85EB_CA6B)
+        ^ axis.wrapping_mul(0xC2B2_AE35);
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x7FEB_352D);
+    hash ^= hash >> 15;
+    (hash & 0xFFFF) as f32 / 32767.5 - 1.0
+}
+
+/// Fully saturated colour at `hue` round the colour wheel, wrapping at 1.
+fn rainbow(hue: f32, alpha: u8) -> Color {
+    let sector = hue.rem_euclid(1.0) * 6.0;
+    let rising = (sector.fract() * 255.0) as u8;
+    let falling = 255 - rising;
+    let (r, g, b) = match sector as u32 {
+        0 => (255, rising, 0),
+        1 => (falling, 255, 0),
+        2 => (0, 255, rising),
+        3 => (0, falling, 255),
+        4 => (rising, 0, 255),
+        _ => (255, 0, falling),
+    };
+    Color::from_rgba(r, g, b, alpha)
+}
+
+/// `brush` with its alpha scaled by `alpha`. Only solid brushes fade.
+fn faded(brush: Brush, alpha: f32) -> Brush {
+    match brush {
+        Brush::Solid(color) => {
+            Brush::Solid(Color::from_rgba(color.r, color.g, color.b, (color.a as f32 * alpha) as u8))
+        }
+        brush => brush,
+    }
+}
+
+/// `push_rect_filled`, with the top edge moved right by `slant` of the height
+/// for fake italics.
+fn push_glyph(
+    drawing_context: &mut DrawingContext,
+    bounds: &Rect<f32>,
+    tex_coords: &[Vector2<f32>; 4],
+    slant: f32,
+) {
+    if slant == 0.0 {
+        drawing_context.push_rect_filled(bounds, Some(tex_coords));
+        return;
+    }
+    let lean = bounds.h() * slant;
+    let index = drawing_context.last_vertex_index();
+    drawing_context.push_vertex(Vector2::new(bounds.x() + lean, bounds.y()), tex_coords[0]);
+    drawing_context.push_vertex(Vector2::new(bounds.x() + bounds.w() + lean, bounds.y()), tex_coords[1]);
+    drawing_context.push_vertex(
+        Vector2::new(bounds.x() + bounds.w(), bounds.y() + bounds.h()),
+        tex_coords[2],
+    );
+    drawing_context.push_vertex(Vector2::new(bounds.x(), bounds.y() + bounds.h()), tex_coords[3]);
+    drawing_context.push_triangle(index, index + 1, index + 2);
+    drawing_context.push_triangle(index, index + 2, index + 3);
+}
+
 pub struct TextBuilder {
     widget_builder: WidgetBuilder,
     text: Option<String>,
     font: Option<SharedFont>,
+    bold_font: Option<SharedFont>,
+    italic_font: Option<SharedFont>,
+    bold_italic_font: Option<SharedFont>,
+    font_size: Option<f32>,
     vertical_text_alignment: VerticalAlignment,
     horizontal_text_alignment: HorizontalAlignment,
     wrap: WrapMode,
@@ -181,6 +814,12 @@ pub struct TextBuilder {
     shadow_brush: Brush,
     shadow_dilation: f32,
     shadow_offset: Vector2<f32>,
+    outline: bool,
+    outline_brush: Brush,
+    outline_thickness: f32,
+    emphasis_brush: Brush,
+    chars_per_second: f32,
+    resource_manager: Option<ResourceManager>,
 }
 
 impl TextBuilder {
@@ -189,6 +828,10 @@ impl TextBuilder {
             widget_builder,
             text: None,
             font: None,
+            bold_font: None,
+            italic_font: None,
+            bold_italic_font: None,
+            font_size: None,
             vertical_text_alignment: VerticalAlignment::Top,
             horizontal_text_alignment: HorizontalAlignment::Left,
             wrap: WrapMode::NoWrap,
@@ -196,6 +839,12 @@ impl TextBuilder {
             shadow_brush: Brush::Solid(Color::BLACK),
             shadow_dilation: 1.0,
             shadow_offset: Vector2::new(1.0, 1.0),
+            outline: false,
+            outline_brush: Brush::Solid(Color::BLACK),
+            outline_thickness: 1.0,
+            emphasis_brush: Brush::Solid(Color::opaque(255, 200, 120)),
+            chars_per_second: 0.0,
+            resource_manager: None,
         }
     }
 
@@ -214,6 +863,29 @@ impl TextBuilder {
         self
     }
 
+    /// Used for `<b>`, faked from the regular font without it.
+    pub fn with_bold_font(mut self, font: SharedFont) -> Self {
+        self.bold_font = Some(font);
+        self
+    }
+
+    /// Used for `<i>`, faked from the regular font without it.
+    pub fn with_italic_font(mut self, font: SharedFont) -> Self {
+        self.italic_font = Some(font);
+        self
+    }
+
+    pub fn with_bold_italic_font(mut self, font: SharedFont) -> Self {
+        self.bold_italic_font = Some(font);
+        self
+    }
+
+    /// In pixels, the font's own height by default. Glyphs are scaled to it.
+    pub fn with_font_size(mut self, size: f32) -> Self {
+        self.font_size = Some(size);
+        self
+    }
+
     pub fn with_vertical_text_alignment(mut self, valign: VerticalAlignment) -> Self {
         self.vertical_text_alignment = valign;
         self
@@ -254,6 +926,41 @@ impl TextBuilder {
         self
     }
 
+    /// Whether the text is outlined, drawn between the shadow and the text.
+    pub fn with_outline(mut self, outline: bool) -> Self {
+        self.outline = outline;
+        self
+    }
+
+    pub fn with_outline_brush(mut self, brush: Brush) -> Self {
+        self.outline_brush = brush;
+        self
+    }
+
+    /// Sets how far the outline reaches out from the glyphs, in pixels.
+    pub fn with_outline_thickness(mut self, thickness: f32) -> Self {
+        self.outline_thickness = thickness;
+        self
+    }
+
+    /// Brush of `<em>` text that has no colour of its own.
+    pub fn with_emphasis_brush(mut self, brush: Brush) -> Self {
+        self.emphasis_brush = brush;
+        self
+    }
+
+    /// Reveals text at this speed instead of all at once.
+    pub fn with_chars_per_second(mut self, chars_per_second: f32) -> Self {
+        self.chars_per_second = chars_per_second;
+        self
+    }
+
+    /// Loads the textures of `<icon>` tags.
+    pub fn with_resource_manager(mut self, resource_manager: ResourceManager) -> Self {
+        self.resource_manager = Some(resource_manager);
+        self
+    }
+
     pub fn build(mut self, ui: &mut BuildContext) -> Handle<UiNode> {
         let font = if let Some(font) = self.font {
             font
@@ -265,21 +972,89 @@ impl TextBuilder {
             self.widget_builder.foreground = Some(Brush::Solid(Color::opaque(220, 220, 220)));
         }
 
+        let markup = parse_markup(&self.text.unwrap_or_default());
+        let mut reveal = Reveal {
+            chars_per_second: self.chars_per_second.max(0.0),
+            ..Default::default()
+        };
+        reveal.start(markup.text.chars().count(), markup.pauses.clone());
+        let icon_textures = request_icons(self.resource_manager.as_ref(), &markup);
         let text = Text {
             widget: self.widget_builder.build(),
-            formatted_text: RefCell::new(
-                FormattedTextBuilder::new(font)
-                    .with_text(self.text.unwrap_or_default())
-                    .with_vertical_alignment(self.vertical_text_alignment)
-                    .with_horizontal_alignment(self.horizontal_text_alignment)
-                    .with_wrap(self.wrap)
-                    .with_shadow(self.shadow)
-                    .with_shadow_brush(self.shadow_brush)
-                    .with_shadow_dilation(self.shadow_dilation)
-                    .with_shadow_offset(self.shadow_offset)
-                    .build(),
-            ),
+            markup,
+            fonts: Fonts {
+                regular: font,
+                bold: self.bold_font,
+                italic: self.italic_font,
+                bold_italic: self.bold_italic_font,
+            },
+            font_size: self.font_size,
+            wrap: self.wrap,
+            vertical_alignment: self.vertical_text_alignment,
+            horizontal_alignment: self.horizontal_text_alignment,
+            shadow: self.shadow,
+            shadow_brush: self.shadow_brush,
+            shadow_dilation: self.shadow_dilation,
+            shadow_offset: self.shadow_offset,
+            outline: self.outline,
+            outline_brush: self.outline_brush,
+            outline_thickness: self.outline_thickness,
+            emphasis_brush: self.emphasis_brush,
+            resource_manager: self.resource_manager,
+            icon_textures,
+            glyphs: RefCell::new(Vec::new()),
+            reveal,
+            time: 0.0,
+            appeared: Vec::new(),
         };
         ui.add_node(UiNode::new(text))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Line texts and widths of `source` laid out with bold letters a pixel
+    /// wider than the rest, the way fake bold is.
+    fn lines(source: &str, wrap: WrapMode, constraint: f32) -> Vec<(String, f32)> {
+        let markup = parse_markup(source);
+        let chars: Vec<char> = markup.text.chars().collect();
+        let advances: Vec<f32> = (0..chars.len())
+            .map(|index| if markup.style_at(index).bold { 11.0 } else { 10.0 })
+            .collect();
+        break_lines(&chars, &advances, wrap, constraint)
+            .into_iter()
+            .map(|line| (chars[line.begin..line.end].iter().collect(), line.width))
+            .collect()
+    }
+
+    #[test]
+    fn words_span_styled_runs() {
+        assert_eq!(
+            lines("<b>Hel</b>lo wor<i>ld</i> again", WrapMode::Word, 110.0),
+            [("Hello ".to_string(), 53.0), ("world again".to_string(), 110.0)]
+        );
+    }
+
+    #[test]
+    fn long_words_break_anywhere() {
+        assert_eq!(
+            lines("abcdefgh", WrapMode::Word, 35.0),
+            [("abc".to_string(), 30.0), ("def".to_string(), 30.0), ("gh".to_string(), 20.0)]
+        );
+        assert_eq!(
+            lines("<b>abcd</b>ef", WrapMode::Letter, 30.0),
+            [("ab".to_string(), 22.0), ("cd".to_string(), 22.0), ("ef".to_string(), 20.0)]
+        );
+    }
+
+    #[test]
+    fn newlines_always_break() {
+        assert_eq!(
+            lines("a <b>b</b>\nc", WrapMode::NoWrap, 10.0),
+            [("a b".to_string(), 31.0), ("c".to_string(), 10.0)]
+        );
+        assert_eq!(lines("", WrapMode::Word, 10.0), [(String::new(), 0.0)]);
+    }
+}
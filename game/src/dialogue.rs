@@ -1,51 +1,39 @@
-use std::collections::HashMap;
+use fyrox::{gui::{brush::Brush, text::{TextMessage, TextBuilder}, text_box::TextBoxBuilder, formatted_text::WrapMode, message::MessageDirection, UiNode, stack_panel::StackPanelBuilder, widget::{WidgetBuilder, WidgetMessage}, BuildContext, button::ButtonBuilder, HorizontalAlignment, VerticalAlignment, UserInterface}, core::{pool::Handle, algebra::Vector2, color::Color}, asset::manager::ResourceManager};
 
-use fyrox::{gui::{text::{TextMessage, TextBuilder}, message::MessageDirection, UiNode, stack_panel::StackPanelBuilder, widget::{WidgetBuilder, WidgetMessage}, BuildContext, button::ButtonBuilder, HorizontalAlignment}, core::{pool::Handle, algebra::Vector2}, asset::manager::ResourceManager};
-
-use crate::{create_nine_box, nine_patch::center_widget_builder};
-pub fn show_dialogue(ui: &&mut fyrox::gui::UserInterface, dialogue_box:Handle<UiNode>, msg: String) {
+use crate::{better_text, create_nine_box, nine_patch::center_widget_builder, runner::{ShownChoice, TopicItem}, theme::{TextTheme, Theme}};
+/// The name plate is hidden for lines without a character.
+pub fn show_dialogue(ui: &&mut fyrox::gui::UserInterface, dialogue_box:Handle<UiNode>, name_plate: Handle<UiNode>, character: String, msg: String) {
     //ui.send_message(WidgetMessage::remove(dialogue_box,MessageDirection::ToWidget));
     ui.send_message(WidgetMessage::visibility(dialogue_box, MessageDirection::ToWidget, true));
-    ui.send_message(TextMessage::text(
+    ui.send_message(WidgetMessage::visibility(name_plate, MessageDirection::ToWidget, !character.is_empty()));
+    ui.send_message(better_text::TextMessage::text(
+        name_plate,
+        MessageDirection::ToWidget,
+        character,
+    ));
+    ui.send_message(better_text::TextMessage::text(
         dialogue_box,
         MessageDirection::ToWidget,
         msg,
     ));
 }
 
+/// Builds a button for every option, in the order the script declares them.
+/// The index of a button in `ChoiceRet::choices` is the index of its option.
 pub fn show_choices(
     ctx: &mut BuildContext,
     resource_manager: &ResourceManager,
-    choices:HashMap::<String, String>
+    theme: &Theme,
+    choices: &[ShownChoice]
 ) -> ChoiceRet {
-    let mut buttons = HashMap::<Handle<UiNode>, String>::new();
-    let mut wb = WidgetBuilder::new();
-    for (choice_name, choice_jump) in choices{
-        let center = TextBuilder::new(
-            center_widget_builder()
-                .with_max_size(Vector2::new(200.0, f32::INFINITY))
-        )
-        .with_text(choice_name)
-        .build(ctx);
-        let nine = create_nine_box(
-            ctx,
-            resource_manager,
-            "data/choice9boxblur.png",
-            40,
-            41,
-            40,
-            41,
-            81,
-            81,
-            Some(center)
-        );
-        let button = ButtonBuilder::new(WidgetBuilder::new()
-            .with_horizontal_alignment(HorizontalAlignment::Center)
-            .with_child(nine)
-        )
-        .build(ctx);
-        wb = wb.with_child(button.clone());
-        buttons.insert(button, choice_jump);
+    let mut buttons = Vec::<Handle<UiNode>>::new();
+    let mut wb = WidgetBuilder::new()
+        .with_horizontal_alignment(HorizontalAlignment::Center)
+        .with_vertical_alignment(VerticalAlignment::Center);
+    for choice in choices {
+        let button = create_choice_button(ctx, resource_manager, theme, &choice.text, choice.enabled);
+        wb = wb.with_child(button);
+        buttons.push(button);
     }
     ChoiceRet {
         choice_container: StackPanelBuilder::new(wb).build(ctx),
@@ -55,7 +43,182 @@ pub fn show_choices(
 
 }
 
+/// Builds the hub for an `offer_topics` item, one button per topic item plus
+/// a "Leave" button when the hub has an exit.
+pub fn show_topics(
+    ctx: &mut BuildContext,
+    resource_manager: &ResourceManager,
+    theme: &Theme,
+    items: Vec<TopicItem>,
+    exit: bool,
+    revision: u64,
+) -> TopicRet {
+    let mut buttons = Vec::<(Handle<UiNode>, String)>::new();
+    let mut wb = WidgetBuilder::new()
+        .with_horizontal_alignment(HorizontalAlignment::Center)
+        .with_vertical_alignment(VerticalAlignment::Center);
+    for item in items {
+        let button = create_choice_button(ctx, resource_manager, theme, &item.label, item.enabled);
+        wb = wb.with_child(button);
+        buttons.push((button, item.item));
+    }
+    let leave = if exit {
+        let button = create_choice_button(ctx, resource_manager, theme, "Leave", true);
+        wb = wb.with_child(button);
+        Some(button)
+    } else {
+        None
+    };
+    TopicRet {
+        topic_container: StackPanelBuilder::new(wb).build(ctx),
+        items: buttons,
+        leave,
+        revision,
+    }
+}
+
+/// Disabled buttons are greyed out and ignore clicks.
+pub fn create_choice_button(
+    ctx: &mut BuildContext,
+    resource_manager: &ResourceManager,
+    theme: &Theme,
+    text: &str,
+    enabled: bool,
+) -> Handle<UiNode> {
+    let text_builder = center_widget_builder()
+        .with_max_size(Vector2::new(200.0, f32::INFINITY));
+    let center = if enabled {
+        theme.text_builder(text_builder, &theme.choice)
+    } else {
+        let disabled = TextTheme {
+            foreground: theme.disabled_choice,
+            ..theme.choice.clone()
+        };
+        theme.text_builder(text_builder, &disabled)
+    }
+    .with_text(text)
+    .build(ctx);
+    let nine = create_nine_box(
+        ctx,
+        resource_manager,
+        "data/choice9boxblur.png",
+        40,
+        41,
+        40,
+        41,
+        81,
+        81,
+        Some(center)
+    );
+    ButtonBuilder::new(WidgetBuilder::new()
+        .with_enabled(enabled)
+        .with_horizontal_alignment(HorizontalAlignment::Center)
+    )
+    .with_content(nine)
+    .build(ctx)
+}
+
 pub struct ChoiceRet {
     pub choice_container:Handle<UiNode>,
-    pub choices: HashMap<Handle<UiNode>, String>
-}
\ No newline at end of file
+    pub choices: Vec<Handle<UiNode>>
+}
+impl ChoiceRet {
+    pub fn index_of(&self, button: Handle<UiNode>) -> Option<usize> {
+        self.choices.iter().position(|&choice| choice == button)
+    }
+    pub fn remove(self, ui: &UserInterface) {
+        ui.send_message(WidgetMessage::remove(self.choice_container, MessageDirection::ToWidget));
+    }
+}
+
+pub struct TopicRet {
+    pub topic_container: Handle<UiNode>,
+    pub items: Vec<(Handle<UiNode>, String)>,
+    pub leave: Option<Handle<UiNode>>,
+    /// `TopicList::revision` the hub was built from.
+    pub revision: u64,
+}
+impl TopicRet {
+    pub fn item_of(&self, button: Handle<UiNode>) -> Option<&str> {
+        self.items
+            .iter()
+            .find(|(item_button, _)| *item_button == button)
+            .map(|(_, item)| item.as_str())
+    }
+    pub fn is_leave(&self, button: Handle<UiNode>) -> bool {
+        self.leave == Some(button)
+    }
+    pub fn remove(self, ui: &UserInterface) {
+        ui.send_message(WidgetMessage::remove(self.topic_container, MessageDirection::ToWidget));
+    }
+}
+
+/// Builds a nine-patch framed prompt with a text box and an OK button.
+pub fn show_text_prompt(
+    ctx: &mut BuildContext,
+    resource_manager: &ResourceManager,
+    prompt: &str,
+) -> PromptRet {
+    let label = TextBuilder::new(WidgetBuilder::new()
+        .with_max_size(Vector2::new(300.0, f32::INFINITY))
+    )
+    .with_wrap(WrapMode::Word)
+    .with_text(prompt)
+    .build(ctx);
+    let text_box = TextBoxBuilder::new(WidgetBuilder::new()
+        .with_min_size(Vector2::new(200.0, 24.0))
+    )
+    .build(ctx);
+    let error = TextBuilder::new(WidgetBuilder::new()
+        .with_foreground(Brush::Solid(Color::opaque(220, 80, 80)))
+    )
+    .build(ctx);
+    let ok = ButtonBuilder::new(WidgetBuilder::new()
+        .with_horizontal_alignment(HorizontalAlignment::Center)
+    )
+    .with_text("OK")
+    .build(ctx);
+    let center = StackPanelBuilder::new(center_widget_builder()
+        .with_child(label)
+        .with_child(text_box)
+        .with_child(error)
+        .with_child(ok)
+    )
+    .build(ctx);
+    let frame = create_nine_box(
+        ctx,
+        resource_manager,
+        "data/9boxblur.png",
+        40,
+        41,
+        40,
+        41,
+        81,
+        81,
+        Some(center)
+    );
+    PromptRet {
+        prompt_container: frame,
+        text_box,
+        error,
+        ok,
+        value: String::new(),
+    }
+}
+
+pub struct PromptRet {
+    pub prompt_container: Handle<UiNode>,
+    pub text_box: Handle<UiNode>,
+    pub error: Handle<UiNode>,
+    pub ok: Handle<UiNode>,
+    /// Mirrors the text box, updated from its `TextMessage::Text` messages.
+    pub value: String,
+}
+impl PromptRet {
+    pub fn show_error(&self, ui: &UserInterface, msg: &str) {
+        ui.send_message(TextMessage::text(self.error, MessageDirection::ToWidget, msg.to_string()));
+    }
+    pub fn remove(self, ui: &UserInterface) {
+        ui.send_message(WidgetMessage::remove(self.prompt_container, MessageDirection::ToWidget));
+    }
+}
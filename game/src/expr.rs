@@ -0,0 +1,623 @@
+//! Script variables and the small expression language used by `set`, `if`
+//! and `condition`.
+//!
+//! Values are numbers, booleans and strings. Expressions support literals
+//! (`1.5`, `true`, `"text"` or `'text'`), variable names, parentheses,
+//! `!`/`not`, unary `-`, `* / %`, `+ -` (`+` joins strings), comparisons
+//! `== != < <= > >=`, `&&`/`and` and `||`/`or`. Assignments are written as
+//! `name = expr`, `name += expr` or `name -= expr`.
+//!
+//! Reading a variable that was never set is an error, `validate` reports the
+//! ones no `set` assigns.
+use std::{collections::BTreeMap, fmt};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Value {
+    Bool(bool),
+    Number(f64),
+    Str(String),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::Str(_) => "string",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Number(value) => write!(f, "{}", value),
+            Value::Str(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Variables(BTreeMap<String, Value>);
+impl Variables {
+    pub fn new() -> Self {
+        Self(BTreeMap::new())
+    }
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.0.get(name)
+    }
+    pub fn set(&mut self, name: &str, value: Value) -> Option<Value> {
+        self.0.insert(name.to_string(), value)
+    }
+    pub fn remove(&mut self, name: &str) -> Option<Value> {
+        self.0.remove(name)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.0.iter()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExprError {
+    pub message: String,
+    /// Byte offset into the expression source.
+    pub offset: usize,
+}
+
+impl ExprError {
+    fn new(message: impl Into<String>, offset: usize) -> Self {
+        Self {
+            message: message.into(),
+            offset,
+        }
+    }
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.offset + 1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Not,
+    Neg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinaryOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Or => "||",
+            BinaryOp::And => "&&",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    Var { name: String, offset: usize },
+    Unary { op: UnaryOp, expr: Box<Expr>, offset: usize },
+    Binary { op: BinaryOp, lhs: Box<Expr>, rhs: Box<Expr>, offset: usize },
+}
+
+impl Expr {
+    pub fn eval(&self, variables: &Variables) -> Result<Value, ExprError> {
+        match self {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Var { name, offset } => variables
+                .get(name)
+                .cloned()
+                .ok_or_else(|| ExprError::new(format!("unknown variable {}", name), *offset)),
+            Expr::Unary { op, expr, offset } => match (op, expr.eval(variables)?) {
+                (UnaryOp::Not, Value::Bool(value)) => Ok(Value::Bool(!value)),
+                (UnaryOp::Neg, Value::Number(value)) => Ok(Value::Number(-value)),
+                (UnaryOp::Not, value) => Err(ExprError::new(
+                    format!("can't negate a {} with !", value.type_name()),
+                    *offset,
+                )),
+                (UnaryOp::Neg, value) => Err(ExprError::new(
+                    format!("can't negate a {} with -", value.type_name()),
+                    *offset,
+                )),
+            },
+            Expr::Binary { op: BinaryOp::And, lhs, rhs, offset } => {
+                if !expect_bool(lhs.eval(variables)?, BinaryOp::And, *offset)? {
+                    return Ok(Value::Bool(false));
+                }
+                Ok(Value::Bool(expect_bool(rhs.eval(variables)?, BinaryOp::And, *offset)?))
+            }
+            Expr::Binary { op: BinaryOp::Or, lhs, rhs, offset } => {
+                if expect_bool(lhs.eval(variables)?, BinaryOp::Or, *offset)? {
+                    return Ok(Value::Bool(true));
+                }
+                Ok(Value::Bool(expect_bool(rhs.eval(variables)?, BinaryOp::Or, *offset)?))
+            }
+            Expr::Binary { op, lhs, rhs, offset } => {
+                binary(*op, lhs.eval(variables)?, rhs.eval(variables)?, *offset)
+            }
+        }
+    }
+
+    /// Names of all variables the expression reads.
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        self.collect_variables(&mut names);
+        names
+    }
+
+    fn collect_variables<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Expr::Literal(_) => {}
+            Expr::Var { name, .. } => names.push(name),
+            Expr::Unary { expr, .. } => expr.collect_variables(names),
+            Expr::Binary { lhs, rhs, .. } => {
+                lhs.collect_variables(names);
+                rhs.collect_variables(names);
+            }
+        }
+    }
+}
+
+fn expect_bool(value: Value, op: BinaryOp, offset: usize) -> Result<bool, ExprError> {
+    match value {
+        Value::Bool(value) => Ok(value),
+        value => Err(ExprError::new(
+            format!("{} needs booleans, got a {}", op.symbol(), value.type_name()),
+            offset,
+        )),
+    }
+}
+
+fn binary(op: BinaryOp, lhs: Value, rhs: Value, offset: usize) -> Result<Value, ExprError> {
+    let result = match (op, &lhs, &rhs) {
+        (BinaryOp::Eq, _, _) => Value::Bool(lhs == rhs),
+        (BinaryOp::Ne, _, _) => Value::Bool(lhs != rhs),
+        (BinaryOp::Add, Value::Number(a), Value::Number(b)) => Value::Number(a + b),
+        (BinaryOp::Add, Value::Str(_), _) | (BinaryOp::Add, _, Value::Str(_)) => {
+            Value::Str(format!("{}{}", lhs, rhs))
+        }
+        (BinaryOp::Sub, Value::Number(a), Value::Number(b)) => Value::Number(a - b),
+        (BinaryOp::Mul, Value::Number(a), Value::Number(b)) => Value::Number(a * b),
+        (BinaryOp::Div, Value::Number(_), Value::Number(b)) if *b == 0.0 => {
+            return Err(ExprError::new("division by zero", offset));
+        }
+        (BinaryOp::Div, Value::Number(a), Value::Number(b)) => Value::Number(a / b),
+        (BinaryOp::Rem, Value::Number(_), Value::Number(b)) if *b == 0.0 => {
+            return Err(ExprError::new("division by zero", offset));
+        }
+        (BinaryOp::Rem, Value::Number(a), Value::Number(b)) => Value::Number(a % b),
+        (BinaryOp::Lt, Value::Number(a), Value::Number(b)) => Value::Bool(a < b),
+        (BinaryOp::Le, Value::Number(a), Value::Number(b)) => Value::Bool(a <= b),
+        (BinaryOp::Gt, Value::Number(a), Value::Number(b)) => Value::Bool(a > b),
+        (BinaryOp::Ge, Value::Number(a), Value::Number(b)) => Value::Bool(a >= b),
+        (BinaryOp::Lt, Value::Str(a), Value::Str(b)) => Value::Bool(a < b),
+        (BinaryOp::Le, Value::Str(a), Value::Str(b)) => Value::Bool(a <= b),
+        (BinaryOp::Gt, Value::Str(a), Value::Str(b)) => Value::Bool(a > b),
+        (BinaryOp::Ge, Value::Str(a), Value::Str(b)) => Value::Bool(a >= b),
+        _ => {
+            return Err(ExprError::new(
+                format!(
+                    "can't apply {} to a {} and a {}",
+                    op.symbol(),
+                    lhs.type_name(),
+                    rhs.type_name()
+                ),
+                offset,
+            ));
+        }
+    };
+    Ok(result)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Ident(String),
+    Op(&'static str),
+}
+
+/// Longest operators first so `<=` isn't read as `<` followed by `=`.
+const OPERATORS: [&str; 21] = [
+    "==", "!=", "<=", ">=", "&&", "||", "+=", "-=", "<", ">", "=", "!", "+", "-", "*", "/", "%",
+    "(", ")", "&", "|",
+];
+
+fn tokenize(src: &str) -> Result<Vec<(Token, usize)>, ExprError> {
+    let mut tokens = Vec::new();
+    let mut chars = src.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_ascii_digit() || c == '.') {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            let number = src[start..end]
+                .parse::<f64>()
+                .map_err(|_| ExprError::new(format!("bad number {}", &src[start..end]), start))?;
+            tokens.push((Token::Number(number), start));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            tokens.push((Token::Ident(src[start..end].to_string()), start));
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut text = String::new();
+            let mut closed = false;
+            while let Some((_, next)) = chars.next() {
+                if next == c {
+                    closed = true;
+                    break;
+                }
+                if next == '\\' {
+                    if let Some((_, escaped)) = chars.next() {
+                        text.push(escaped);
+                    }
+                    continue;
+                }
+                text.push(next);
+            }
+            if !closed {
+                return Err(ExprError::new("unterminated string", start));
+            }
+            tokens.push((Token::Str(text), start));
+        } else {
+            let Some(op) = OPERATORS.iter().find(|op| src[start..].starts_with(**op)) else {
+                return Err(ExprError::new(format!("unexpected character {:?}", c), start));
+            };
+            if *op == "&" || *op == "|" {
+                return Err(ExprError::new(format!("use {}{} instead of {}", op, op, op), start));
+            }
+            for _ in 0..op.len() {
+                chars.next();
+            }
+            tokens.push((Token::Op(op), start));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn new(src: &str) -> Result<Self, ExprError> {
+        Ok(Self {
+            tokens: tokenize(src)?,
+            pos: 0,
+            end: src.len(),
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |(_, offset)| *offset)
+    }
+
+    /// Consumes the next token if it's one of `ops`, or the keyword that
+    /// spells the same operator.
+    fn eat_op(&mut self, ops: &[&str]) -> Option<(&'static str, usize)> {
+        let offset = self.offset();
+        let op = match self.peek()? {
+            Token::Op(op) if ops.contains(op) => *op,
+            Token::Ident(word) if word == "and" && ops.contains(&"&&") => "&&",
+            Token::Ident(word) if word == "or" && ops.contains(&"||") => "||",
+            Token::Ident(word) if word == "not" && ops.contains(&"!") => "!",
+            _ => return None,
+        };
+        self.pos += 1;
+        Some((op, offset))
+    }
+
+    fn expect_end(&self) -> Result<(), ExprError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => Err(ExprError::new(format!("unexpected {:?}", token), self.offset())),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, ExprError> {
+        let mut lhs = self.and()?;
+        while let Some((_, offset)) = self.eat_op(&["||"]) {
+            let rhs = self.and()?;
+            lhs = binary_expr(BinaryOp::Or, lhs, rhs, offset);
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Expr, ExprError> {
+        let mut lhs = self.equality()?;
+        while let Some((_, offset)) = self.eat_op(&["&&"]) {
+            let rhs = self.equality()?;
+            lhs = binary_expr(BinaryOp::And, lhs, rhs, offset);
+        }
+        Ok(lhs)
+    }
+
+    fn equality(&mut self) -> Result<Expr, ExprError> {
+        let mut lhs = self.comparison()?;
+        while let Some((op, offset)) = self.eat_op(&["==", "!="]) {
+            let rhs = self.comparison()?;
+            let op = if op == "==" { BinaryOp::Eq } else { BinaryOp::Ne };
+            lhs = binary_expr(op, lhs, rhs, offset);
+        }
+        Ok(lhs)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ExprError> {
+        let mut lhs = self.additive()?;
+        while let Some((op, offset)) = self.eat_op(&["<", "<=", ">", ">="]) {
+            let rhs = self.additive()?;
+            let op = match op {
+                "<" => BinaryOp::Lt,
+                "<=" => BinaryOp::Le,
+                ">" => BinaryOp::Gt,
+                _ => BinaryOp::Ge,
+            };
+            lhs = binary_expr(op, lhs, rhs, offset);
+        }
+        Ok(lhs)
+    }
+
+    fn additive(&mut self) -> Result<Expr, ExprError> {
+        let mut lhs = self.multiplicative()?;
+        while let Some((op, offset)) = self.eat_op(&["+", "-"]) {
+            let rhs = self.multiplicative()?;
+            let op = if op == "+" { BinaryOp::Add } else { BinaryOp::Sub };
+            lhs = binary_expr(op, lhs, rhs, offset);
+        }
+        Ok(lhs)
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, ExprError> {
+        let mut lhs = self.unary()?;
+        while let Some((op, offset)) = self.eat_op(&["*", "/", "%"]) {
+            let rhs = self.unary()?;
+            let op = match op {
+                "*" => BinaryOp::Mul,
+                "/" => BinaryOp::Div,
+                _ => BinaryOp::Rem,
+            };
+            lhs = binary_expr(op, lhs, rhs, offset);
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ExprError> {
+        if let Some((op, offset)) = self.eat_op(&["!", "-"]) {
+            let op = if op == "!" { UnaryOp::Not } else { UnaryOp::Neg };
+            return Ok(Expr::Unary {
+                op,
+                expr: Box::new(self.unary()?),
+                offset,
+            });
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, ExprError> {
+        let offset = self.offset();
+        let Some(token) = self.peek().cloned() else {
+            return Err(ExprError::new("expected a value", offset));
+        };
+        self.pos += 1;
+        match token {
+            Token::Number(number) => Ok(Expr::Literal(Value::Number(number))),
+            Token::Str(text) => Ok(Expr::Literal(Value::Str(text))),
+            Token::Ident(word) if word == "true" => Ok(Expr::Literal(Value::Bool(true))),
+            Token::Ident(word) if word == "false" => Ok(Expr::Literal(Value::Bool(false))),
+            Token::Ident(name) => Ok(Expr::Var { name, offset }),
+            Token::Op("(") => {
+                let expr = self.or()?;
+                if self.eat_op(&[")"]).is_none() {
+                    return Err(ExprError::new("expected )", self.offset()));
+                }
+                Ok(expr)
+            }
+            token => Err(ExprError::new(format!("unexpected {:?}", token), offset)),
+        }
+    }
+}
+
+fn binary_expr(op: BinaryOp, lhs: Expr, rhs: Expr, offset: usize) -> Expr {
+    Expr::Binary {
+        op,
+        lhs: Box::new(lhs),
+        rhs: Box::new(rhs),
+        offset,
+    }
+}
+
+pub fn parse_expr(src: &str) -> Result<Expr, ExprError> {
+    let mut parser = Parser::new(src)?;
+    let expr = parser.or()?;
+    parser.expect_end()?;
+    Ok(expr)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    pub name: String,
+    /// Already combined with the old value for `+=` and `-=`.
+    pub expr: Expr,
+}
+
+impl Assignment {
+    pub fn apply(&self, variables: &mut Variables) -> Result<Value, ExprError> {
+        let value = self.expr.eval(variables)?;
+        variables.set(&self.name, value.clone());
+        Ok(value)
+    }
+}
+
+pub fn parse_assignment(src: &str) -> Result<Assignment, ExprError> {
+    let mut parser = Parser::new(src)?;
+    let offset = parser.offset();
+    let Some(Token::Ident(name)) = parser.peek().cloned() else {
+        return Err(ExprError::new("expected a variable name", offset));
+    };
+    parser.pos += 1;
+    let Some((op, op_offset)) = parser.eat_op(&["=", "+=", "-="]) else {
+        return Err(ExprError::new("expected =, += or -=", parser.offset()));
+    };
+    let rhs = parser.or()?;
+    parser.expect_end()?;
+    let expr = match op {
+        "+=" | "-=" => binary_expr(
+            if op == "+=" { BinaryOp::Add } else { BinaryOp::Sub },
+            Expr::Var {
+                name: name.clone(),
+                offset,
+            },
+            rhs,
+            op_offset,
+        ),
+        _ => rhs,
+    };
+    Ok(Assignment { name, expr })
+}
+
+/// Parses and evaluates `src`, which has to produce a boolean.
+pub fn eval_condition(src: &str, variables: &Variables) -> Result<bool, ExprError> {
+    match parse_expr(src)?.eval(variables)? {
+        Value::Bool(value) => Ok(value),
+        value => Err(ExprError::new(
+            format!("condition is a {}, not a boolean", value.type_name()),
+            0,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(src: &str, variables: &Variables) -> Result<Value, ExprError> {
+        parse_expr(src)?.eval(variables)
+    }
+
+    fn error(src: &str) -> (String, usize) {
+        let err = eval(src, &Variables::new()).unwrap_err();
+        (err.message, err.offset)
+    }
+
+    #[test]
+    fn precedence() {
+        let variables = Variables::new();
+        assert_eq!(eval("1 + 2 * 3 - 4 / 2", &variables), Ok(Value::Number(5.0)));
+        assert_eq!(eval("(1 + 2) * 3", &variables), Ok(Value::Number(9.0)));
+        assert_eq!(eval("-2 * 3 % 4", &variables), Ok(Value::Number(-2.0)));
+        assert_eq!(eval("1 + 1 == 2 && 3 < 2 || !false", &variables), Ok(Value::Bool(true)));
+        assert_eq!(eval("true || false && false", &variables), Ok(Value::Bool(true)));
+        assert_eq!(eval("not true or 1 >= 1 and 'a' < 'b'", &variables), Ok(Value::Bool(true)));
+    }
+
+    #[test]
+    fn short_circuit() {
+        // The right side would fail on the unset variable.
+        let variables = Variables::new();
+        assert_eq!(eval("false && missing", &variables), Ok(Value::Bool(false)));
+        assert_eq!(eval("true || missing", &variables), Ok(Value::Bool(true)));
+    }
+
+    #[test]
+    fn assignments() {
+        let mut variables = Variables::new();
+        parse_assignment("name = \"Bob\"").unwrap().apply(&mut variables).unwrap();
+        parse_assignment("name += ' the ' + 2").unwrap().apply(&mut variables).unwrap();
+        assert_eq!(variables.get("name"), Some(&Value::Str("Bob the 2".to_string())));
+        parse_assignment("count = 5").unwrap().apply(&mut variables).unwrap();
+        parse_assignment("count -= 1 + 1").unwrap().apply(&mut variables).unwrap();
+        assert_eq!(variables.get("count"), Some(&Value::Number(3.0)));
+        let err = parse_assignment("missing += 1").unwrap().apply(&mut variables).unwrap_err();
+        assert_eq!(err, ExprError::new("unknown variable missing", 0));
+        assert_eq!(variables.get("missing"), None);
+        let err = parse_assignment("name -= 1").unwrap().apply(&mut variables).unwrap_err();
+        assert_eq!(err, ExprError::new("can't apply - to a string and a number", 5));
+    }
+
+    #[test]
+    fn type_errors() {
+        assert_eq!(error("1 + true"), ("can't apply + to a number and a boolean".to_string(), 2));
+        assert_eq!(error("'a' < 1"), ("can't apply < to a string and a number".to_string(), 4));
+        assert_eq!(error("!1"), ("can't negate a number with !".to_string(), 0));
+        assert_eq!(error("-'a'"), ("can't negate a string with -".to_string(), 0));
+        assert_eq!(error("1 && true"), ("&& needs booleans, got a number".to_string(), 2));
+        assert_eq!(error("1 / 0"), ("division by zero".to_string(), 2));
+        assert_eq!(
+            eval_condition("1 + 1", &Variables::new()),
+            Err(ExprError::new("condition is a number, not a boolean", 0))
+        );
+        assert_eq!(eval("1 == 'a'", &Variables::new()), Ok(Value::Bool(false)));
+    }
+
+    #[test]
+    fn error_offsets() {
+        let offset = |src: &str| parse_expr(src).unwrap_err().offset;
+        assert_eq!(offset("1 + "), 4);
+        assert_eq!(offset("(1 + 2"), 6);
+        assert_eq!(offset("1 2"), 2);
+        assert_eq!(offset("'open"), 0);
+        assert_eq!(offset("a & b"), 2);
+        assert_eq!(offset("a # b"), 2);
+        assert_eq!(error("1 + unset"), ("unknown variable unset".to_string(), 4));
+        assert_eq!(parse_assignment("1 = 2").unwrap_err().offset, 0);
+        assert_eq!(parse_assignment("a == 2").unwrap_err().offset, 2);
+        assert_eq!(
+            ExprError::new("expected a value", 4).to_string(),
+            "expected a value at column 5"
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! What the player has read so far, for the backlog panel.
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+/// How many entries `History::new` keeps before dropping the oldest.
+pub const HISTORY_LIMIT: usize = 200;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum HistoryEntry {
+    /// A blurp as it was shown, i.e. translated and filled in.
+    Line { character: String, text: String },
+    /// The text of a choice option or topic item the player picked.
+    Choice(String),
+}
+
+/// The last `limit` lines and choices, oldest first. Only the entries go into
+/// save files, the limit comes from whoever loads them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "VecDeque<HistoryEntry>", into = "VecDeque<HistoryEntry>")]
+pub struct History {
+    entries: VecDeque<HistoryEntry>,
+    limit: usize,
+    /// Bumped on every change so an open backlog can tell it's stale.
+    revision: u64,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<VecDeque<HistoryEntry>> for History {
+    fn from(entries: VecDeque<HistoryEntry>) -> Self {
+        let mut history = Self::new();
+        history.entries = entries;
+        history.trim();
+        history
+    }
+}
+
+impl From<History> for VecDeque<HistoryEntry> {
+    fn from(history: History) -> Self {
+        history.entries
+    }
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::with_limit(HISTORY_LIMIT)
+    }
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            limit,
+            revision: 0,
+        }
+    }
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+    /// Drops the oldest entries if there are more than `limit` already.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.trim();
+        self.revision += 1;
+    }
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+    pub fn push(&mut self, entry: HistoryEntry) {
+        self.entries.push_back(entry);
+        self.trim();
+        self.revision += 1;
+    }
+    /// Swaps in `other`'s entries, keeping this history's limit.
+    pub fn replace(&mut self, other: History) {
+        self.entries = other.entries;
+        self.trim();
+        self.revision += 1;
+    }
+    /// Takes the newest entry off again.
+    pub fn pop(&mut self) -> Option<HistoryEntry> {
+        let entry = self.entries.pop_back();
+        self.revision += 1;
+        entry
+    }
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &HistoryEntry> {
+        self.entries.iter()
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.revision += 1;
+    }
+    fn trim(&mut self) {
+        while self.entries.len() > self.limit {
+            self.entries.pop_front();
+        }
+    }
+}
@@ -0,0 +1,930 @@
+//! Importers for dialogue written in Ink or Yarn Spinner.
+//!
+//! Only the parts that map onto script items are understood. For Ink that's
+//! knots and stitches, diverts, `*`/`+` choices with conditions and `[]` text,
+//! gathers, `VAR`/`CONST`, `~` assignments and `{variable}` in text. For Yarn
+//! it's nodes, `->` options with `<<if>>` conditions, `<<jump>>`, `<<stop>>`,
+//! `<<set>>`, `<<declare>>` and `{$variable}` in text, other commands become
+//! actions. Tags become cues in both, except for Yarn's `#line:` ids, which
+//! become string ids.
+//!
+//! Everything else is reported with its line and skipped, or kept as plain
+//! text where that's the best there is. Option bodies and what follows the
+//! options get scripts of their own, named after the knot or node with `.c1`,
+//! `.g1` and so on added. Choices can always be picked again, Ink's once-only
+//! `*` choices are reported and imported like `+` ones.
+use std::{collections::HashMap, path::Path};
+
+use crate::{
+    expr::{parse_assignment, parse_expr},
+    location::{Location, ScriptLocations},
+    script::{Action, Blurp, Choice, ChoiceOption, Cue, End, Jump, ScriptItem, Scripts, Set},
+    validate::{is_terminator, Issue, Severity},
+};
+
+pub const INK_EXTENSION: &str = "ink";
+pub const YARN_EXTENSION: &str = "yarn";
+
+#[derive(Debug, Clone, Default)]
+pub struct Imported {
+    pub scripts: Scripts,
+    /// Everything that couldn't be imported as written.
+    pub issues: Vec<Issue>,
+}
+
+/// Imports `data` as Ink or Yarn by `file`'s extension, `None` for anything
+/// else.
+pub fn import_file(data: &str, file: &Path) -> Option<Imported> {
+    let extension = file.extension()?.to_str()?;
+    match extension {
+        INK_EXTENSION => Some(import_ink(data, file)),
+        YARN_EXTENSION => Some(import_yarn(data, file)),
+        _ => None,
+    }
+}
+
+struct ImportedScript {
+    name: String,
+    /// The knot, stitch or node the script was made for, generated scripts
+    /// are named after it.
+    base: String,
+    items: Vec<ScriptItem>,
+    locations: ScriptLocations,
+}
+
+/// A choice that's still collecting options.
+struct OpenChoice {
+    owner: usize,
+    item: usize,
+    /// Option scripts that continue after the choice unless they jump away.
+    bodies: Vec<usize>,
+    indent: usize,
+}
+
+struct Builder<'a> {
+    file: &'a Path,
+    line: usize,
+    scripts: Vec<ImportedScript>,
+    issues: Vec<Issue>,
+    /// Script new items go to, `None` after something that can't be reached.
+    current: Option<usize>,
+    choice: Option<OpenChoice>,
+    generated: HashMap<(String, &'static str), usize>,
+}
+
+impl<'a> Builder<'a> {
+    fn new(file: &'a Path) -> Self {
+        Self {
+            file,
+            line: 0,
+            scripts: Vec::new(),
+            issues: Vec::new(),
+            current: None,
+            choice: None,
+            generated: HashMap::new(),
+        }
+    }
+
+    fn location(&self) -> Location {
+        Location::new(self.file, self.line, 1)
+    }
+
+    fn issue(&mut self, severity: Severity, message: impl Into<String>) {
+        let script = self
+            .current
+            .or(self.scripts.len().checked_sub(1))
+            .map(|index| self.scripts[index].name.clone())
+            .unwrap_or_default();
+        self.issues.push(Issue {
+            severity,
+            script,
+            index: None,
+            message: message.into(),
+            location: Some(self.location()),
+        });
+    }
+
+    fn unsupported(&mut self, message: impl Into<String>) {
+        self.issue(Severity::Warning, message);
+    }
+
+    fn add_script(&mut self, name: String, base: String) -> usize {
+        self.scripts.push(ImportedScript {
+            name,
+            base,
+            items: Vec::new(),
+            locations: ScriptLocations {
+                script: self.location(),
+                items: Vec::new(),
+            },
+        });
+        self.scripts.len() - 1
+    }
+
+    /// Starts a knot, stitch or node, closing whatever came before it.
+    fn start(&mut self, name: String) -> usize {
+        self.close_choice();
+        let script = self.add_script(name.clone(), name);
+        self.current = Some(script);
+        script
+    }
+
+    /// A new script for an option body or gather after `owner`.
+    fn add_generated(&mut self, owner: usize, kind: &'static str) -> usize {
+        let base = self.scripts[owner].base.clone();
+        let count = self.generated.entry((base.clone(), kind)).or_default();
+        *count += 1;
+        let name = format!("{}.{}{}", base, kind, count);
+        self.add_script(name, base)
+    }
+
+    fn push(&mut self, item: ScriptItem) {
+        let Some(current) = self.current else {
+            self.unsupported("line can't be reached, it follows a divert");
+            return;
+        };
+        let location = self.location();
+        let script = &mut self.scripts[current];
+        script.items.push(item);
+        script.locations.items.push(location);
+    }
+
+    /// Adds an option to the open choice, starting one if needed. Options
+    /// without a `jump` get a script of their own that takes the lines after
+    /// them.
+    fn option(&mut self, text: String, condition: Option<String>, jump: Option<String>, indent: usize) {
+        if self.choice.is_none() {
+            let Some(owner) = self.current else {
+                self.unsupported("option can't be reached, it follows a divert");
+                return;
+            };
+            self.push(ScriptItem::Choice(Choice::default()));
+            self.choice = Some(OpenChoice {
+                owner,
+                item: self.scripts[owner].items.len() - 1,
+                bodies: Vec::new(),
+                indent,
+            });
+        }
+        let Some(OpenChoice { owner, item, .. }) = self.choice else {
+            return;
+        };
+        let (jump, body) = match jump {
+            Some(jump) => (jump, None),
+            None => {
+                let body = self.add_generated(owner, "c");
+                (self.scripts[body].name.clone(), Some(body))
+            }
+        };
+        if let ScriptItem::Choice(choice) = &mut self.scripts[owner].items[item] {
+            choice.choice.push(ChoiceOption {
+                jump,
+                text,
+                condition,
+                show_disabled: false,
+                id: None,
+                note: None,
+            });
+        }
+        if let (Some(body), Some(choice)) = (body, &mut self.choice) {
+            choice.bodies.push(body);
+        }
+        self.current = body;
+    }
+
+    /// Ends the open choice, option bodies that don't jump away continue in a
+    /// new script.
+    fn gather(&mut self) {
+        let Some(choice) = self.choice.take() else {
+            return;
+        };
+        let gather = self.add_generated(choice.owner, "g");
+        let name = self.scripts[gather].name.clone();
+        let location = self.location();
+        for body in choice.bodies {
+            let script = &mut self.scripts[body];
+            if !script.items.last().is_some_and(is_terminator) {
+                script.items.push(ScriptItem::Jump(Jump {
+                    jump: name.clone(),
+                    note: None,
+                }));
+                script.locations.items.push(location.clone());
+            }
+        }
+        self.current = Some(gather);
+    }
+
+    fn close_choice(&mut self) {
+        self.choice = None;
+    }
+
+    fn blurp(&mut self, character: String, text: String) {
+        self.push(ScriptItem::Blurp(Blurp {
+            character,
+            text,
+            ..Default::default()
+        }));
+    }
+
+    /// Gives the blurp just added a string id.
+    fn set_blurp_id(&mut self, id: Option<String>) {
+        let Some(current) = self.current else {
+            return;
+        };
+        if let Some(ScriptItem::Blurp(blurp)) = self.scripts[current].items.last_mut() {
+            blurp.id = id;
+        }
+    }
+
+    /// Gives the option just added a string id.
+    fn set_option_id(&mut self, id: Option<String>) {
+        let Some(OpenChoice { owner, item, .. }) = self.choice else {
+            return;
+        };
+        if let ScriptItem::Choice(choice) = &mut self.scripts[owner].items[item] {
+            if let Some(option) = choice.choice.last_mut() {
+                option.id = id;
+            }
+        }
+    }
+
+    fn cues(&mut self, tags: Vec<String>) {
+        for cue in tags {
+            self.push(ScriptItem::Cue(Cue { cue, note: None }));
+        }
+    }
+
+    fn set(&mut self, assignment: String) {
+        match parse_assignment(&assignment) {
+            Ok(_) => self.push(ScriptItem::Set(Set {
+                set: assignment,
+                note: None,
+            })),
+            Err(err) => self.unsupported(format!("assignment `{}` isn't supported: {}", assignment, err)),
+        }
+    }
+
+    /// Adds a declaration to the script made for the file, after the ones
+    /// before it. Declarations hold wherever they're written, so they go first
+    /// and only run once.
+    fn declare(&mut self, assignment: String) {
+        let current = self.current.replace(0);
+        let choice = self.choice.take();
+        let count = self.scripts[0].items.len();
+        self.set(assignment);
+        let script = &mut self.scripts[0];
+        if script.items.len() > count {
+            let item = script.items.pop().unwrap_or_default();
+            let location = script.locations.items.pop().unwrap_or_default();
+            let first = script.items.iter().take_while(|item| matches!(item, ScriptItem::Set(_))).count();
+            script.items.insert(first, item);
+            script.locations.items.insert(first, location);
+        }
+        self.current = current;
+        self.choice = choice;
+    }
+
+    /// Drops the script made for the file if nothing went into it, or renames
+    /// it if a knot or node has its name.
+    fn finish_root(&mut self, root: &str) {
+        if self.scripts[0].items.is_empty() {
+            self.scripts.remove(0);
+        } else if self.scripts[1..].iter().any(|script| script.name == root) {
+            self.scripts[0].name = format!("{}.root", root);
+        }
+    }
+
+    fn condition(&mut self, condition: String) -> String {
+        if let Err(err) = parse_expr(&condition) {
+            self.unsupported(format!("condition `{}` isn't supported: {}", condition, err));
+        }
+        condition
+    }
+
+    fn jump(&mut self, target: String) {
+        self.push(ScriptItem::Jump(Jump {
+            jump: target,
+            note: None,
+        }));
+        self.current = None;
+    }
+
+    fn end(&mut self) {
+        self.push(ScriptItem::End(End::default()));
+        self.current = None;
+    }
+
+    fn finish(mut self) -> Imported {
+        let mut scripts = Scripts::default();
+        for script in std::mem::take(&mut self.scripts) {
+            if scripts.has_script(&script.name) {
+                self.line = script.locations.script.line;
+                self.current = None;
+                self.issue(Severity::Error, format!("script {} is defined twice", script.name));
+                continue;
+            }
+            scripts.insert(script.name.clone(), script.items);
+            scripts.set_locations(&script.name, script.locations);
+        }
+        Imported {
+            scripts,
+            issues: self.issues,
+        }
+    }
+}
+
+/// Splits off the `#` tags at the end of a line.
+fn split_tags(line: &str) -> (&str, Vec<String>) {
+    match line.find('#') {
+        Some(index) => (
+            &line[..index],
+            line[index + 1..]
+                .split('#')
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect(),
+        ),
+        None => (line, Vec::new()),
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find("//") {
+        Some(index) => &line[..index],
+        None => line,
+    }
+}
+
+/// Splits `Name: text` into the character and text, `Name` has to be a single
+/// word. Anything else is narration by no one in particular.
+fn split_character(text: &str) -> (String, String) {
+    if let Some((name, rest)) = text.split_once(':') {
+        let name = name.trim();
+        if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return (name.to_string(), rest.trim().to_string());
+        }
+    }
+    (String::new(), text.trim().to_string())
+}
+
+/// Turns `{name}` in text into a `[name]` placeholder and escapes brackets.
+/// `sigil` is stripped from the start of names, `$` for Yarn.
+fn convert_text(builder: &mut Builder, text: &str, sigil: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(['{', '[', ']']) {
+        out.push_str(&rest[..start]);
+        let c = rest[start..].chars().next().unwrap_or_default();
+        if c != '{' {
+            out.push(c);
+            out.push(c);
+            rest = &rest[start + 1..];
+            continue;
+        }
+        let Some(end) = rest[start..].find('}') else {
+            builder.unsupported("unclosed { in text");
+            out.push_str(&rest[start..]);
+            return out;
+        };
+        let inner = rest[start + 1..start + end].trim();
+        let name = inner.strip_prefix(sigil).unwrap_or(inner);
+        if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            out.push('[');
+            out.push_str(name);
+            out.push(']');
+        } else {
+            builder.unsupported(format!("inline logic `{{{}}}` isn't supported, kept as text", inner));
+            out.push_str(&rest[start..=start + end]);
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// A divert target, with `END` and `DONE` as `None`.
+fn ink_target(target: &str) -> Option<String> {
+    match target {
+        "END" | "DONE" => None,
+        target => Some(target.to_string()),
+    }
+}
+
+/// Splits the divert off the end of Ink content.
+fn ink_divert(builder: &mut Builder, content: &str) -> (String, Option<Option<String>>) {
+    let Some(index) = content.find("->") else {
+        return (content.to_string(), None);
+    };
+    let target = content[index + 2..].trim();
+    if target.is_empty() || target.contains("->") || content[index + 2..].starts_with('>') {
+        builder.unsupported("tunnels aren't supported");
+        return (content[..index].to_string(), None);
+    }
+    if target.contains(char::is_whitespace) || target.contains('(') {
+        builder.unsupported(format!("divert `-> {}` isn't supported", target));
+        return (content[..index].to_string(), None);
+    }
+    (content[..index].to_string(), Some(ink_target(target)))
+}
+
+/// Adds a line of Ink content: text, tags and an optional divert.
+fn ink_content(builder: &mut Builder, content: &str) {
+    let (content, tags) = split_tags(content);
+    let (mut text, divert) = ink_divert(builder, content);
+    if text.contains("<>") {
+        builder.unsupported("glue isn't supported");
+        text = text.replace("<>", "");
+    }
+    if !text.trim().is_empty() {
+        let (character, text) = split_character(&text);
+        let text = convert_text(builder, &text, "");
+        builder.blurp(character, text);
+    }
+    builder.cues(tags);
+    match divert {
+        Some(Some(target)) => builder.jump(target),
+        Some(None) => builder.end(),
+        None => {}
+    }
+}
+
+/// Counts leading `marker`s, spaces between them allowed, returning the count
+/// and the rest.
+fn count_markers(line: &str, marker: char) -> (usize, &str) {
+    let mut count = 0;
+    let mut rest = line;
+    loop {
+        let trimmed = rest.trim_start();
+        match trimmed.strip_prefix(marker) {
+            Some(after) if !(marker == '-' && after.starts_with('>')) => {
+                count += 1;
+                rest = after;
+            }
+            _ => return (count, trimmed),
+        }
+    }
+}
+
+/// Drops a `(label)` from the start of a choice or gather.
+fn strip_label(text: &str) -> &str {
+    let text = text.trim_start();
+    if text.starts_with('(') {
+        if let Some(end) = text.find(')') {
+            return text[end + 1..].trim_start();
+        }
+    }
+    text
+}
+
+fn ink_choice(builder: &mut Builder, line: &str, indent: usize) {
+    let mut rest = strip_label(line);
+    let mut conditions = Vec::new();
+    while let Some(after) = rest.strip_prefix('{') {
+        let Some(end) = after.find('}') else {
+            break;
+        };
+        conditions.push(after[..end].trim().to_string());
+        rest = after[end + 1..].trim_start();
+    }
+    let (rest, tags) = split_tags(rest);
+    let (text, divert) = ink_divert(builder, rest);
+    // `a [b] c` shows `a b` as the option and prints `a c` once picked.
+    let (menu, output) = match (text.find('['), text.find(']')) {
+        (Some(open), Some(close)) if open < close => (
+            format!("{}{}", &text[..open], &text[open + 1..close]),
+            format!("{}{}", &text[..open], &text[close + 1..]),
+        ),
+        _ => (text.clone(), text.clone()),
+    };
+    let knots: Vec<String> = builder.scripts.iter().map(|script| script.base.clone()).collect();
+    for condition in &conditions {
+        if knots.iter().any(|knot| condition.split(|c: char| !c.is_alphanumeric() && c != '_' && c != '.').any(|word| word == knot)) {
+            builder.unsupported(format!("visit counts in `{}` aren't supported", condition));
+        }
+    }
+    let condition = match conditions.len() {
+        0 => None,
+        1 => Some(builder.condition(conditions.remove(0))),
+        _ => {
+            let joined = conditions.iter().map(|condition| format!("({})", condition)).collect::<Vec<_>>().join(" && ");
+            Some(builder.condition(joined))
+        }
+    };
+    let menu = convert_text(builder, menu.trim(), "");
+    if let Some(Some(target)) = &divert {
+        if output.trim().is_empty() && tags.is_empty() {
+            builder.option(menu, condition, Some(target.clone()), indent);
+            return;
+        }
+    }
+    builder.option(menu, condition, None, indent);
+    if !output.trim().is_empty() {
+        let output = convert_text(builder, output.trim(), "");
+        builder.blurp(String::new(), output);
+    }
+    builder.cues(tags);
+    match divert {
+        Some(Some(target)) => builder.jump(target),
+        Some(None) => builder.end(),
+        None => {}
+    }
+}
+
+/// Points diverts at stitches of the same knot, `-> stitch` inside `knot`
+/// means `knot.stitch`.
+fn resolve_stitches(builder: &mut Builder) {
+    let names: Vec<String> = builder.scripts.iter().map(|script| script.name.clone()).collect();
+    for script in &mut builder.scripts {
+        let knot = script.base.split('.').next().unwrap_or_default().to_string();
+        let resolve = |target: &mut String| {
+            let local = format!("{}.{}", knot, target);
+            if !names.contains(target) && names.contains(&local) {
+                *target = local;
+            }
+        };
+        for item in &mut script.items {
+            match item {
+                ScriptItem::Jump(jump) => resolve(&mut jump.jump),
+                ScriptItem::Choice(choice) => {
+                    for option in &mut choice.choice {
+                        resolve(&mut option.jump);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Imports the supported subset of an Ink story. Content before the first
+/// knot, and `VAR`s, go into a script named after the file.
+pub fn import_ink(data: &str, file: &Path) -> Imported {
+    let mut builder = Builder::new(file);
+    let root = file
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("root")
+        .to_string();
+    builder.start(root.clone());
+    let mut in_comment = false;
+    let mut skip_function = false;
+    let mut block_depth = 0;
+    let mut knot: Option<String> = None;
+    // A knot without content of its own continues into its first stitch.
+    let mut empty_knot: Option<usize> = None;
+    for (index, line) in data.lines().enumerate() {
+        builder.line = index + 1;
+        let mut line = line.to_string();
+        if in_comment {
+            match line.find("*/") {
+                Some(end) => {
+                    line = line[end + 2..].to_string();
+                    in_comment = false;
+                }
+                None => continue,
+            }
+        }
+        while let Some(start) = line.find("/*") {
+            match line[start..].find("*/") {
+                Some(end) => line.replace_range(start..start + end + 2, ""),
+                None => {
+                    line.truncate(start);
+                    in_comment = true;
+                }
+            }
+        }
+        let line = strip_comment(&line);
+        let indent = line.len() - line.trim_start().len();
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("TODO") {
+            continue;
+        }
+        if block_depth > 0 {
+            block_depth += line.matches('{').count() as i32 - line.matches('}').count() as i32;
+            continue;
+        }
+        if line.starts_with("==") {
+            let name = line.trim_matches('=').trim();
+            skip_function = name.starts_with("function ");
+            if skip_function {
+                builder.current = None;
+                builder.close_choice();
+                knot = None;
+                builder.unsupported(format!("{} isn't supported, skipped", name));
+                continue;
+            }
+            let name = match name.split_once('(') {
+                Some((name, _)) => {
+                    builder.unsupported(format!("knot parameters of {} aren't supported", name.trim()));
+                    name.trim()
+                }
+                None => name,
+            };
+            empty_knot = Some(builder.start(name.to_string()));
+            knot = Some(name.to_string());
+            continue;
+        }
+        if skip_function {
+            continue;
+        }
+        if let Some(stitch) = line.strip_prefix('=') {
+            let stitch = stitch.trim();
+            let Some(knot) = &knot else {
+                builder.unsupported("stitch outside of a knot, skipped");
+                continue;
+            };
+            let name = format!("{}.{}", knot, stitch);
+            if let Some(knot) = empty_knot.take().filter(|knot| builder.scripts[*knot].items.is_empty()) {
+                builder.current = Some(knot);
+                builder.jump(name.clone());
+            }
+            builder.start(name);
+            continue;
+        }
+        if let Some(declaration) = line.strip_prefix("VAR ").or_else(|| line.strip_prefix("CONST ")) {
+            builder.declare(declaration.trim().to_string());
+            continue;
+        }
+        if let Some(logic) = line.strip_prefix('~') {
+            let logic = logic.trim();
+            let logic = logic.strip_prefix("temp ").unwrap_or(logic).trim();
+            if let Some(name) = logic.strip_suffix("++") {
+                builder.set(format!("{} += 1", name.trim()));
+            } else if let Some(name) = logic.strip_suffix("--") {
+                builder.set(format!("{} -= 1", name.trim()));
+            } else if logic.starts_with("return") || logic.contains('(') {
+                builder.unsupported(format!("`~ {}` isn't supported", logic));
+            } else {
+                builder.set(logic.to_string());
+            }
+            continue;
+        }
+        let keyword = line.split_whitespace().next().unwrap_or_default();
+        if ["INCLUDE", "LIST", "EXTERNAL"].contains(&keyword) {
+            builder.unsupported(format!("{} isn't supported", keyword));
+            continue;
+        }
+        if line.starts_with("<-") {
+            builder.unsupported("threads aren't supported");
+            continue;
+        }
+        if line.starts_with('{') && line.matches('{').count() > line.matches('}').count() {
+            builder.unsupported("multiline conditional blocks aren't supported, skipped");
+            block_depth = line.matches('{').count() as i32 - line.matches('}').count() as i32;
+            continue;
+        }
+        if line.starts_with('*') || line.starts_with('+') {
+            let marker = if line.starts_with('*') { '*' } else { '+' };
+            let (depth, rest) = count_markers(line, marker);
+            if depth > 1 {
+                builder.unsupported("nested choices aren't supported, skipped");
+                continue;
+            }
+            if marker == '*' {
+                builder.unsupported("once-only choices aren't supported, it can be picked again");
+            }
+            ink_choice(&mut builder, rest, indent);
+            continue;
+        }
+        if line.starts_with('-') && !line.starts_with("->") {
+            let (depth, rest) = count_markers(line, '-');
+            if depth > 1 {
+                builder.unsupported("nested gathers aren't supported, treated as a plain gather");
+            }
+            builder.gather();
+            let rest = strip_label(rest);
+            if !rest.is_empty() {
+                ink_content(&mut builder, rest);
+            }
+            continue;
+        }
+        ink_content(&mut builder, line);
+    }
+    builder.finish_root(&root);
+    resolve_stitches(&mut builder);
+    builder.finish()
+}
+
+/// Turns a Yarn expression into one of ours.
+fn yarn_expression(expression: &str) -> String {
+    let mut out = String::new();
+    let mut word = String::new();
+    let mut quote = None;
+    let flush = |word: &mut String, out: &mut String| {
+        out.push_str(match word.as_str() {
+            "is" | "eq" => "==",
+            "neq" => "!=",
+            "gt" => ">",
+            "lt" => "<",
+            "gte" => ">=",
+            "lte" => "<=",
+            "to" => "=",
+            word => word,
+        });
+        word.clear();
+    };
+    for c in expression.chars() {
+        match quote {
+            Some(q) => {
+                out.push(c);
+                if c == q {
+                    quote = None;
+                }
+            }
+            None if c == '"' || c == '\'' => {
+                flush(&mut word, &mut out);
+                quote = Some(c);
+                out.push(c);
+            }
+            None if c.is_alphanumeric() || c == '_' => word.push(c),
+            None if c == '$' => {}
+            None => {
+                flush(&mut word, &mut out);
+                out.push(c);
+            }
+        }
+    }
+    flush(&mut word, &mut out);
+    out.trim().to_string()
+}
+
+/// Splits a `<<if condition>>` off the end of an option.
+fn yarn_option_condition(text: &str) -> (&str, Option<&str>) {
+    if let Some(start) = text.find("<<if") {
+        if let Some(end) = text[start..].find(">>") {
+            return (&text[..start], Some(text[start + 4..start + end].trim()));
+        }
+    }
+    (text, None)
+}
+
+/// Splits Yarn's `#line:` id, which becomes the string id, off the tags.
+fn yarn_tags(tags: Vec<String>) -> (Option<String>, Vec<String>) {
+    let mut id = None;
+    let cues = tags
+        .into_iter()
+        .filter(|tag| match tag.strip_prefix("line:") {
+            Some(line) => {
+                id = Some(format!("line:{}", line));
+                false
+            }
+            None => true,
+        })
+        .collect();
+    (id, cues)
+}
+
+fn yarn_text(builder: &mut Builder, text: &str) -> String {
+    let mut text = text.to_string();
+    while let Some(start) = text.find("<<") {
+        let end = text[start..].find(">>").map_or(text.len(), |end| start + end + 2);
+        builder.unsupported(format!("inline command `{}` isn't supported, dropped", &text[start..end]));
+        text.replace_range(start..end, "");
+    }
+    if text.contains('[') {
+        builder.unsupported("markup isn't supported, kept as text");
+    }
+    convert_text(builder, text.trim(), "$")
+}
+
+fn yarn_command(builder: &mut Builder, command: &str, if_depth: &mut usize) {
+    let (name, rest) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+    let rest = rest.trim();
+    match name {
+        "jump" if !rest.contains('{') => builder.jump(rest.to_string()),
+        "stop" => builder.end(),
+        "set" => builder.set(yarn_expression(rest)),
+        "declare" => {
+            let assignment = match rest.split_once(" as ") {
+                Some((assignment, _)) => assignment,
+                None => rest,
+            };
+            builder.declare(yarn_expression(assignment));
+        }
+        "if" => {
+            builder.unsupported("<<if>> blocks aren't supported, skipped");
+            *if_depth = 1;
+        }
+        "elseif" | "else" | "endif" => builder.unsupported(format!("<<{}>> without <<if>>", name)),
+        "jump" | "wait" | "call" | "local" | "once" | "detour" | "return" => {
+            builder.unsupported(format!("<<{}>> isn't supported", command))
+        }
+        _ => builder.push(ScriptItem::Action(Action {
+            action: command.to_string(),
+            note: None,
+        })),
+    }
+}
+
+/// Imports the supported subset of a Yarn Spinner project file.
+/// `<<declare>>`s go into a script named after the file, which then jumps to
+/// the first node, so start there to have them set once.
+pub fn import_yarn(data: &str, file: &Path) -> Imported {
+    let mut builder = Builder::new(file);
+    let root = file
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("root")
+        .to_string();
+    builder.start(root.clone());
+    builder.current = None;
+    let mut in_body = false;
+    let mut title: Option<String> = None;
+    let mut if_depth = 0;
+    for (index, line) in data.lines().enumerate() {
+        builder.line = index + 1;
+        if !in_body {
+            let line = line.trim();
+            if line == "---" {
+                in_body = true;
+                let name = title.take().unwrap_or_else(|| {
+                    builder.unsupported("node without a title");
+                    format!("node{}", builder.line)
+                });
+                builder.start(name);
+            } else if let Some((key, value)) = line.split_once(':') {
+                if key.trim() == "title" {
+                    title = Some(value.trim().to_string());
+                }
+            }
+            continue;
+        }
+        let line = strip_comment(line);
+        let indent = line.chars().take_while(|c| c.is_whitespace()).map(|c| if c == '\t' { 4 } else { 1 }).sum();
+        let line = line.trim();
+        if line == "===" {
+            builder.close_choice();
+            builder.current = None;
+            in_body = false;
+            if_depth = 0;
+            continue;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        if if_depth > 0 {
+            if line.starts_with("<<if") {
+                if_depth += 1;
+            } else if line.starts_with("<<endif") {
+                if_depth -= 1;
+            }
+            continue;
+        }
+        let option_indent = builder.choice.as_ref().map(|choice| choice.indent);
+        if let Some(text) = line.strip_prefix("->") {
+            if option_indent.is_some_and(|option_indent| indent > option_indent) {
+                builder.unsupported("nested options aren't supported, skipped");
+                continue;
+            }
+            let (text, tags) = split_tags(text);
+            let (text, condition) = yarn_option_condition(text);
+            let condition = condition.map(|condition| builder.condition(yarn_expression(condition)));
+            let text = yarn_text(&mut builder, text);
+            let (id, cues) = yarn_tags(tags);
+            builder.option(text, condition, None, indent);
+            builder.set_option_id(id);
+            builder.cues(cues);
+            continue;
+        }
+        if option_indent.is_some_and(|option_indent| indent <= option_indent) {
+            builder.gather();
+        }
+        if line.starts_with("[[") {
+            builder.unsupported("Yarn 1 [[links]] aren't supported");
+            continue;
+        }
+        if let Some(command) = line.strip_prefix("<<") {
+            let (command, tags) = split_tags(command);
+            let Some(command) = command.trim().strip_suffix(">>") else {
+                builder.unsupported("command is missing its closing >>");
+                continue;
+            };
+            builder.cues(yarn_tags(tags).1);
+            yarn_command(&mut builder, command.trim(), &mut if_depth);
+            continue;
+        }
+        let (text, tags) = split_tags(line);
+        let (character, text) = split_character(text);
+        let text = yarn_text(&mut builder, &text);
+        let (id, cues) = yarn_tags(tags);
+        builder.blurp(character, text);
+        builder.set_blurp_id(id);
+        builder.cues(cues);
+    }
+    if in_body {
+        builder.unsupported("last node is missing its closing ===");
+    }
+    if !builder.scripts[0].items.is_empty() {
+        if let Some(first) = builder.scripts.get(1) {
+            let jump = ScriptItem::Jump(Jump {
+                jump: first.name.clone(),
+                note: None,
+            });
+            let location = first.locations.script.clone();
+            builder.scripts[0].items.push(jump);
+            builder.scripts[0].locations.items.push(location);
+        }
+    }
+    builder.finish_root(&root);
+    builder.finish()
+}
@@ -4,41 +4,109 @@ pub mod topic_list;
 
 pub mod script;
 
+pub mod location;
+
+pub mod screenplay;
+
+pub mod import;
+
+pub mod localization;
+
 pub mod dialogue;
 
-/*
+pub mod runner;
+
+pub mod history;
+mod backlog;
+
+pub mod auto_advance;
+
+pub mod expr;
+
+pub mod placeholder;
+
+pub mod action;
+
+pub mod quest;
+mod quest_log;
+
+pub mod save;
+
+pub mod seen;
+
+pub mod manifest;
+
+pub mod validate;
+
 mod script_resource;
-use script_resource::{ScriptResource, ScriptResourceLoader};
-*/
+use script_resource::{
+    FontResource, FontResourceLoader, ManifestResource, ManifestResourceLoader, ScriptResource, ScriptResourceLoader,
+    StringTableResource, StringTableResourceLoader,
+};
+
 pub mod grid;
 
 mod nine_patch;
 mod nine_patch_widget;
-use dialogue::show_dialogue;
+
+pub mod markup;
+mod better_text;
+mod theme;
+use action::ActionRequest;
+use auto_advance::AutoAdvance;
+use backlog::{build_backlog, scroll_to_latest, BacklogRet};
+use dialogue::{show_choices, show_dialogue, show_text_prompt, show_topics, ChoiceRet, PromptRet, TopicRet};
+use quest_log::{build_quest_log, remove_quest_log};
+use runner::{DialogueEvent, DialogueInput, DialogueRunner, RunnerState};
+use localization::{CSV_EXTENSION, LOCALE_DIR, PO_EXTENSION};
+use theme::{FontSlot, Theme};
+use save::{read_slot, write_slot, SaveGame};
+use seen::{read_seen, write_seen, SkipMode};
 use nine_patch::{create_nine_box, center_widget_builder, NinePatchBuilder};
 
+use serde::{Deserialize, Serialize};
+
+use std::sync::mpsc::{channel, Receiver};
+
 use fyrox::{
-    asset::manager::ResourceManager,
+    asset::{event::ResourceEvent, manager::ResourceManager, Resource},
     core::{algebra::Vector2, color::Color, pool::Handle, log::Log},
     dpi::PhysicalSize,
     engine::GraphicsContext,
-    event::{Event, WindowEvent},
+    event::{ElementState, Event, MouseScrollDelta, WindowEvent},
     event_loop::ControlFlow,
+    keyboard::{KeyCode, PhysicalKey},
     gui::{
         button::{ButtonBuilder, ButtonMessage},
         message::{MessageDirection, UiMessage},
         stack_panel::StackPanelBuilder,
         widget::{WidgetBuilder, WidgetMessage},
-        BuildContext,
-        UiNode, text::{TextBuilder}, formatted_text::WrapMode, image::ImageBuilder, border::BorderBuilder,
+        BuildContext, Thickness,
+        UiNode, UserInterface, text::{TextBuilder, TextMessage}, formatted_text::WrapMode, image::ImageBuilder,
+        ttf::SharedFont,
     },
     plugin::{Plugin, PluginConstructor, PluginContext, PluginRegistrationContext},
     scene::Scene, resource::texture::Texture, utils::into_gui_texture,
 };
+const QUICK_SAVE_SLOT: &str = "quick";
+/// Script the main button starts when no dialogue is running.
+pub const START_SCRIPT: &str = "Pharaoh1";
+const MANIFEST_PATH: &str = "data/scripts.manifest";
+
 pub struct GameConstructor;
 
 impl PluginConstructor for GameConstructor {
-    fn register(&self, #[allow(unused_variables)] context: PluginRegistrationContext) {}
+    fn register(&self, context: PluginRegistrationContext) {
+        let mut state = context.resource_manager.state();
+        state.constructors_container.add::<ScriptResource>();
+        state.constructors_container.add::<ManifestResource>();
+        state.constructors_container.add::<StringTableResource>();
+        state.constructors_container.add::<FontResource>();
+        state.loaders.set(ScriptResourceLoader);
+        state.loaders.set(ManifestResourceLoader);
+        state.loaders.set(StringTableResourceLoader);
+        state.loaders.set(FontResourceLoader);
+    }
     fn create_instance(
         &self,
         _override_scene: Handle<Scene>,
@@ -63,16 +131,22 @@ impl PluginConstructor for GameConstructor {
         .with_text("about you")
         .build(ctx);
   */  
+  let theme = Theme::default();
+  let name_plate = theme.text_builder(WidgetBuilder::new()
+    .with_visibility(false)
+    .with_margin(Thickness::bottom(4.0)), &theme.name_plate)
+    .build(ctx);
+  let dialogue_text = theme.text_builder(WidgetBuilder::new(), &theme.dialogue)
+    .with_wrap(WrapMode::Word)
+    .with_chars_per_second(better_text::DIALOGUE_CHARS_PER_SECOND)
+    .with_resource_manager(resource_manager.clone())
+    .build(ctx);
   let nine = nine_patch_widget::NinePatchBuilder::new(center_widget_builder()
-    .with_child(
-        BorderBuilder::new(center_widget_builder())
-//            .with_texture(into_gui_texture(
-  //              resource_manager.request::<Texture, _>("data/9test.png"),
-    //        ))
-           .build(ctx)
-        )
-
-    )
+    .with_child(StackPanelBuilder::new(WidgetBuilder::new()
+      .with_child(name_plate)
+      .with_child(dialogue_text)
+    ).build(ctx))
+  )
 //  let nine = ImageBuilder::new(center_widget_builder())
   .with_texture(into_gui_texture(
                 resource_manager.request::<Texture, _>("data/9test.png"),
@@ -80,7 +154,15 @@ impl PluginConstructor for GameConstructor {
             .build(ctx);
 
 
-        let scripts = script::load_from_file("data/scripts.json").unwrap();
+        // Listen before requesting, so the first load can't be missed.
+        let (sender, resource_events) = channel();
+        resource_manager.state().event_broadcaster.add(sender);
+        let manifest = resource_manager.request::<ManifestResource, _>(MANIFEST_PATH);
+        let fonts = Theme::request_fonts(resource_manager);
+        let strings = locale().map(|locale| {
+            resource_manager.request::<StringTableResource, _>(format!("{}/{}.{}", LOCALE_DIR, locale, PO_EXTENSION))
+        });
+        watch_for_changes(resource_manager);
         /*
         println!("\n\n");
         match script {
@@ -91,23 +173,399 @@ impl PluginConstructor for GameConstructor {
 
     
 
-        Box::new(Game { button, text:nine, scripts, current_script_pos: None })
+        let mut runner = DialogueRunner::new();
+        runner.set_seen(read_seen());
+        let seen_written = runner.seen().revision();
+
+        Box::new(Game { button, text:nine, dialogue_text, name_plate, theme, fonts, shown_line: None, manifest, script_files: Vec::new(), strings, resource_events, scripts: script::Scripts::default(), scripts_loaded: false, scripts_failed: false, load_failures: 0, runner, seen_written, skip: SkipMode::Off, auto: AutoAdvance::new(), choice_menu: None, topic_menu: None, prompt: None, quest_log: None, backlog: None })
     }
 }
 
+/// Lets edited scripts be reloaded while the game runs.
+#[cfg(not(any(target_arch = "wasm32", target_os = "android")))]
+fn watch_for_changes(resource_manager: &ResourceManager) {
+    use fyrox::core::watcher::FileSystemWatcher;
+    match FileSystemWatcher::new(".", std::time::Duration::from_secs(1)) {
+        Ok(watcher) => resource_manager.state().set_watcher(Some(watcher)),
+        Err(err) => Log::warn(format!("can't watch for changed scripts: {:?}", err)),
+    }
+}
+
+#[cfg(any(target_arch = "wasm32", target_os = "android"))]
+fn watch_for_changes(_resource_manager: &ResourceManager) {}
+
+/// The language to show dialogue in, the one the scripts are written in when
+/// `None`. Set with the `BLEEPING_LOCALE` environment variable, e.g. `de`.
+#[cfg(not(any(target_arch = "wasm32", target_os = "android")))]
+fn locale() -> Option<String> {
+    std::env::var("BLEEPING_LOCALE").ok().filter(|locale| !locale.is_empty())
+}
+
+#[cfg(any(target_arch = "wasm32", target_os = "android"))]
+fn locale() -> Option<String> {
+    None
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ScriptPos {
     pub script: String,
     pub index: usize,
 }
+impl ScriptPos {
+    pub fn next(&self) -> Self {
+        Self { script: self.script.clone(), index: self.index + 1 }
+    }
+}
+
+/// A line as sent to `dialogue_text`. Reaching the same blurp again adds a
+/// history entry, so it counts as a new line, a reload of the scripts doesn't.
+#[derive(PartialEq)]
+struct ShownLine {
+    pos: Option<ScriptPos>,
+    history: u64,
+    text: String,
+}
 
 struct Game {
     button: Handle<UiNode>,
     text: Handle<UiNode>,
+    dialogue_text: Handle<UiNode>,
+    /// Who's speaking, above `dialogue_text`.
+    name_plate: Handle<UiNode>,
+    theme: Theme,
+    /// Loading into `theme`.
+    fonts: Vec<(FontSlot, Resource<FontResource>)>,
+    /// The line `dialogue_text` was last sent.
+    shown_line: Option<ShownLine>,
     
+    manifest: Resource<ManifestResource>,
+    /// One per file the manifest lists.
+    script_files: Vec<Resource<ScriptResource>>,
+    /// Translations for the chosen locale, if there is one.
+    strings: Option<Resource<StringTableResource>>,
+    resource_events: Receiver<ResourceEvent>,
+    /// All files merged, empty until every file has loaded.
     scripts: script::Scripts,
-    current_script_pos: Option<ScriptPos>
-    
+    scripts_loaded: bool,
+    /// The first load failed and that was logged. Failures don't send a
+    /// resource event, so they're polled for until the scripts load.
+    scripts_failed: bool,
+    /// Manifest and script files that had failed to load when last polled.
+    load_failures: usize,
+    runner: DialogueRunner,
+    /// `SeenLines::revision` last written to disk.
+    seen_written: u64,
+    skip: SkipMode,
+    auto: AutoAdvance,
+    choice_menu: Option<ChoiceRet>,
+    topic_menu: Option<TopicRet>,
+    prompt: Option<PromptRet>,
+    quest_log: Option<Handle<UiNode>>,
+    backlog: Option<BacklogRet>,
+}
 
+impl Game {
+    fn handle_events(&mut self, ui: &mut UserInterface, resource_manager: &ResourceManager) {
+        let mut quests_changed = false;
+        for event in self.runner.drain_events() {
+            match event {
+                DialogueEvent::AddQuest { .. }
+                | DialogueEvent::CompleteQuest(_)
+                | DialogueEvent::FailQuest(_) => quests_changed = true,
+                event => Log::info(format!("unhandled dialogue event {:?}", event)),
+            }
+        }
+        if quests_changed {
+            if let Some(panel) = self.quest_log.take() {
+                remove_quest_log(ui, panel);
+                self.quest_log = Some(build_quest_log(&mut ui.build_ctx(), resource_manager, self.runner.quests()));
+            }
+        }
+        self.write_seen();
+    }
+    fn write_seen(&mut self) {
+        let revision = self.runner.seen().revision();
+        if revision == self.seen_written {
+            return;
+        }
+        match write_seen(self.runner.seen()) {
+            Ok(()) => self.seen_written = revision,
+            Err(err) => Log::err(format!("failed to write seen lines: {}", err)),
+        }
+    }
+    /// Whether the line on screen is still being typed out.
+    fn dialogue_revealing(&self, ui: &UserInterface) -> bool {
+        ui.node(self.dialogue_text)
+            .cast::<better_text::Text>()
+            .is_some_and(|text| text.is_revealing())
+    }
+    /// The backlog and quest log hold up timed lines and auto mode.
+    fn panel_open(&self) -> bool {
+        self.backlog.is_some() || self.quest_log.is_some()
+    }
+    fn toggle_auto(&mut self) {
+        self.auto.toggle();
+        Log::info(format!("auto mode: {}", if self.auto.enabled { "on" } else { "off" }));
+    }
+    fn toggle_skip(&mut self) {
+        self.skip = self.skip.next();
+        Log::info(format!("skip mode: {:?}", self.skip));
+    }
+    /// Advances one line per frame while skip mode is on, and turns it off
+    /// once there's something to read or pick.
+    fn skip_line(&mut self, context: &mut PluginContext) {
+        let skips = match self.runner.state() {
+            RunnerState::ShowingLine(line) | RunnerState::WaitingForTimer { line, .. } => self.skip.skips(line),
+            _ => false,
+        };
+        if skips && self.backlog.is_none() {
+            self.apply_input(context, DialogueInput::Continue);
+        } else if !skips {
+            self.skip = SkipMode::Off;
+        }
+    }
+    fn save_to_slot(&self, slot: &str) {
+        match write_slot(slot, &SaveGame::capture(&self.runner)) {
+            Ok(()) => Log::info(format!("saved to slot {}.", slot)),
+            Err(err) => Log::err(format!("failed to save to slot {}: {}", slot, err)),
+        }
+    }
+    fn load_from_slot(&mut self, slot: &str, ui: &mut UserInterface, resource_manager: &ResourceManager) {
+        let loaded = read_slot(slot).and_then(|save| save.apply(&mut self.runner, &self.scripts));
+        if let Err(err) = loaded {
+            Log::err(format!("failed to load slot {}: {}", slot, err));
+            return;
+        }
+        Log::info(format!("loaded slot {}.", slot));
+        self.refresh_after_restore(ui, resource_manager);
+    }
+    fn rollback(&mut self, ui: &mut UserInterface, resource_manager: &ResourceManager) {
+        if !self.runner.rollback(&self.scripts, 1) {
+            Log::info("nothing to roll back to.".to_string());
+            return;
+        }
+        self.refresh_after_restore(ui, resource_manager);
+    }
+    /// Rebuilds everything that shows runner state after it was replaced
+    /// wholesale by a load or rollback.
+    fn refresh_after_restore(&mut self, ui: &mut UserInterface, resource_manager: &ResourceManager) {
+        self.close_menus(ui);
+        if let Some(panel) = self.quest_log.take() {
+            remove_quest_log(ui, panel);
+            self.quest_log = Some(build_quest_log(&mut ui.build_ctx(), resource_manager, self.runner.quests()));
+        }
+        self.runner.drain_events();
+        self.auto.reset();
+        self.sync_ui(ui, resource_manager);
+    }
+    fn close_menus(&mut self, ui: &mut UserInterface) {
+        if let Some(menu) = self.choice_menu.take() {
+            menu.remove(ui);
+        }
+        if let Some(menu) = self.topic_menu.take() {
+            menu.remove(ui);
+        }
+        if let Some(prompt) = self.prompt.take() {
+            prompt.remove(ui);
+        }
+    }
+    /// Requests the files the manifest lists once it's loaded and merges
+    /// them whenever one of them is edited, carrying on from the same place.
+    fn poll_script_resources(&mut self, ui: &mut UserInterface, resource_manager: &ResourceManager) {
+        let events: Vec<ResourceEvent> = self.resource_events.try_iter().collect();
+        let mut changed = false;
+        for event in events {
+            let resource = match event {
+                ResourceEvent::Loaded(resource) | ResourceEvent::Reloaded(resource) => resource,
+                _ => continue,
+            };
+            let path = resource.path();
+            if path == self.manifest.path() {
+                let files = self.manifest.data_ref().files.clone();
+                self.script_files = files
+                    .iter()
+                    .map(|file| resource_manager.request::<ScriptResource, _>(file))
+                    .collect();
+                changed = true;
+            } else if self.script_files.iter().any(|file| file.path() == path) {
+                changed = true;
+            } else if self.strings.as_ref().is_some_and(|strings| strings.path() == path) {
+                self.apply_strings(ui, resource_manager);
+            } else if let Some((slot, font)) = self.fonts.iter().find(|(_, font)| font.path() == path) {
+                if let Some(font) = font.data_ref().font.clone() {
+                    self.apply_font(ui, *slot, font);
+                }
+            }
+        }
+        if changed {
+            self.merge_scripts(ui, resource_manager);
+        }
+        if let Some(strings) = self.strings.as_ref().filter(|strings| !strings.is_loading() && !strings.is_ok()) {
+            let path = strings.path();
+            if path.extension().is_some_and(|extension| extension == PO_EXTENSION) {
+                let csv = path.with_extension(CSV_EXTENSION);
+                Log::info(format!("{} failed to load, trying {}.", path.display(), csv.display()));
+                self.strings = Some(resource_manager.request::<StringTableResource, _>(csv));
+            } else {
+                Log::err(format!("{} failed to load, the text won't be translated.", path.display()));
+                self.strings = None;
+            }
+        }
+        if self.scripts_loaded || self.manifest.is_loading() || self.script_files.iter().any(|file| file.is_loading()) {
+            return;
+        }
+        let failures = usize::from(!self.manifest.is_ok()) + self.script_files.iter().filter(|file| !file.is_ok()).count();
+        // Only try again once a failed file is fixed, or it'd log every frame.
+        if self.scripts_failed && failures == self.load_failures {
+            return;
+        }
+        self.load_failures = failures;
+        if !self.manifest.is_ok() {
+            Log::err(format!("{} failed to load, there are no scripts.", self.manifest.path().display()));
+            self.scripts_failed = true;
+        } else if !self.script_files.is_empty() {
+            // Logs the files that failed.
+            self.merge_scripts(ui, resource_manager);
+            self.scripts_failed = !self.scripts_loaded;
+        }
+    }
+    fn merge_scripts(&mut self, ui: &mut UserInterface, resource_manager: &ResourceManager) {
+        if self.script_files.iter().any(|file| file.is_loading()) {
+            return;
+        }
+        if let Some(failed) = self.script_files.iter().find(|file| !file.is_ok()) {
+            Log::err(format!("{} failed to load, keeping the old scripts.", failed.path().display()));
+            return;
+        }
+        let merged = {
+            let files: Vec<_> = self.script_files.iter().map(|file| (file.path(), file.data_ref())).collect();
+            manifest::merge(files.iter().map(|(path, file)| (path.as_path(), &file.scripts)))
+        };
+        self.scripts = match merged {
+            Ok((scripts, _)) => scripts,
+            Err(err) => {
+                Log::err(format!("{}, keeping the old scripts.", err));
+                return;
+            }
+        };
+        self.scripts_failed = false;
+        if !self.scripts_loaded {
+            self.scripts_loaded = true;
+            return;
+        }
+        Log::info(format!("reloaded {}.", MANIFEST_PATH));
+        self.runner.reload(&self.scripts);
+        self.close_menus(ui);
+        self.handle_events(ui, resource_manager);
+        self.sync_ui(ui, resource_manager);
+    }
+    /// Menus built from now on get `font` from the theme, the text that's
+    /// already there is told about it.
+    fn apply_font(&mut self, ui: &mut UserInterface, slot: FontSlot, font: SharedFont) {
+        self.theme.set_font(slot, font.clone());
+        for text in [self.dialogue_text, self.name_plate] {
+            let message = match slot {
+                FontSlot::Regular => better_text::TextMessage::font(text, MessageDirection::ToWidget, font.clone()),
+                FontSlot::Bold => better_text::TextMessage::bold_font(text, MessageDirection::ToWidget, font.clone()),
+                FontSlot::Italic => better_text::TextMessage::italic_font(text, MessageDirection::ToWidget, font.clone()),
+                FontSlot::BoldItalic => {
+                    better_text::TextMessage::bold_italic_font(text, MessageDirection::ToWidget, font.clone())
+                }
+            };
+            ui.send_message(message);
+        }
+    }
+    fn apply_strings(&mut self, ui: &mut UserInterface, resource_manager: &ResourceManager) {
+        let Some(strings) = &self.strings else {
+            return;
+        };
+        Log::info(format!("loaded {}.", strings.path().display()));
+        self.runner.set_strings(strings.data_ref().table.clone());
+        if self.scripts_loaded && !self.runner.is_finished() {
+            self.runner.reload(&self.scripts);
+            self.close_menus(ui);
+            self.handle_events(ui, resource_manager);
+            self.sync_ui(ui, resource_manager);
+        }
+    }
+    fn toggle_quest_log(&mut self, ui: &mut UserInterface, resource_manager: &ResourceManager) {
+        match self.quest_log.take() {
+            Some(panel) => remove_quest_log(ui, panel),
+            None => {
+                self.quest_log = Some(build_quest_log(&mut ui.build_ctx(), resource_manager, self.runner.quests()));
+            }
+        }
+    }
+    fn open_backlog(&mut self, ui: &mut UserInterface, resource_manager: &ResourceManager) {
+        if let Some(backlog) = self.backlog.take() {
+            backlog.remove(ui);
+        }
+        let backlog = build_backlog(&mut ui.build_ctx(), resource_manager, self.runner.history());
+        scroll_to_latest(ui, &backlog);
+        self.backlog = Some(backlog);
+    }
+    fn toggle_backlog(&mut self, ui: &mut UserInterface, resource_manager: &ResourceManager) {
+        match self.backlog.take() {
+            Some(backlog) => backlog.remove(ui),
+            None => self.open_backlog(ui, resource_manager),
+        }
+    }
+    fn sync_ui(&mut self, ui: &mut UserInterface, resource_manager: &ResourceManager) {
+        let revision = self.runner.history().revision();
+        if self.backlog.as_ref().is_some_and(|backlog| backlog.revision != revision) {
+            self.open_backlog(ui, resource_manager);
+        }
+        match self.runner.state() {
+            RunnerState::ShowingLine(line) | RunnerState::WaitingForTimer { line, .. } => {
+                // Sending the line again would start its reveal over.
+                let shown = ShownLine {
+                    pos: self.runner.position().cloned(),
+                    history: self.runner.history().revision(),
+                    text: line.text.clone(),
+                };
+                if self.shown_line.as_ref() != Some(&shown) {
+                    show_dialogue(&ui, self.dialogue_text, self.name_plate, line.character.clone(), line.text.clone());
+                    self.shown_line = Some(shown);
+                }
+            }
+            RunnerState::WaitingForChoice(options) => {
+                if self.choice_menu.is_none() {
+                    self.choice_menu = Some(show_choices(&mut ui.build_ctx(), resource_manager, &self.theme, options));
+                }
+            }
+            RunnerState::WaitingForTopic { exit, .. } => {
+                let revision = self.runner.topics().revision();
+                if self.topic_menu.as_ref().map(|menu| menu.revision) != Some(revision) {
+                    if let Some(menu) = self.topic_menu.take() {
+                        menu.remove(ui);
+                    }
+                    let items = self.runner.topic_items();
+                    self.topic_menu = Some(show_topics(&mut ui.build_ctx(), resource_manager, &self.theme, items, *exit, revision));
+                }
+            }
+            RunnerState::WaitingForAction(ActionRequest::TextInput { prompt, .. }) => {
+                if self.prompt.is_none() {
+                    self.prompt = Some(show_text_prompt(&mut ui.build_ctx(), resource_manager, prompt));
+                }
+            }
+            _ => {}
+        }
+    }
+    fn apply_input(&mut self, context: &mut PluginContext, input: DialogueInput) {
+        if let Some(menu) = self.choice_menu.take() {
+            menu.remove(context.user_interface);
+        }
+        if let Some(menu) = self.topic_menu.take() {
+            menu.remove(context.user_interface);
+        }
+        if let Some(prompt) = self.prompt.take() {
+            prompt.remove(context.user_interface);
+        }
+        self.runner.handle_input(&self.scripts, input);
+        self.auto.reset();
+        self.handle_events(context.user_interface, context.resource_manager);
+        self.sync_ui(context.user_interface, context.resource_manager);
+    }
 }
 
 impl Plugin for Game {
@@ -122,10 +580,39 @@ impl Plugin for Game {
             .renderer
             .set_backbuffer_clear_color(Color::GREEN);
     }
+    fn update(&mut self, context: &mut PluginContext, _control_flow: &mut ControlFlow) {
+        self.poll_script_resources(context.user_interface, context.resource_manager);
+        if self.skip != SkipMode::Off {
+            self.skip_line(context);
+        }
+        match self.runner.state() {
+            RunnerState::WaitingForTimer { .. }
+                if self.auto.enabled
+                    && !self.panel_open()
+                    && !self.dialogue_revealing(context.user_interface) =>
+            {
+                self.runner.update(&self.scripts, context.dt as f64);
+                self.handle_events(context.user_interface, context.resource_manager);
+                self.sync_ui(context.user_interface, context.resource_manager);
+            }
+            RunnerState::ShowingLine(line)
+                if !self.panel_open() && !self.dialogue_revealing(context.user_interface) =>
+            {
+                if self.auto.update(line, context.dt as f64) {
+                    self.apply_input(context, DialogueInput::Continue);
+                }
+            }
+            // Picks up items added or consumed while the hub is open.
+            RunnerState::WaitingForTopic { .. } => {
+                self.sync_ui(context.user_interface, context.resource_manager);
+            }
+            _ => {}
+        }
+    }
     fn on_os_event(
         &mut self,
         event: &fyrox::event::Event<()>,
-        context: PluginContext,
+        mut context: PluginContext,
         control_flow: &mut ControlFlow,
     ) {
         if let Event::WindowEvent { event, .. } = event {
@@ -134,6 +621,44 @@ impl Plugin for Game {
                 WindowEvent::Resized(size) => {
                     foobar(self.text, context, size);
                 }
+                WindowEvent::KeyboardInput { event: input, .. } => {
+                    if input.state == ElementState::Pressed && !input.repeat {
+                        match input.physical_key {
+                            PhysicalKey::Code(KeyCode::KeyJ) => {
+                                self.toggle_quest_log(context.user_interface, context.resource_manager);
+                            }
+                            PhysicalKey::Code(KeyCode::KeyH) => {
+                                self.toggle_backlog(context.user_interface, context.resource_manager);
+                            }
+                            PhysicalKey::Code(KeyCode::Tab) => self.toggle_skip(),
+                            PhysicalKey::Code(KeyCode::KeyA) => self.toggle_auto(),
+                            PhysicalKey::Code(KeyCode::Backspace) => {
+                                self.rollback(context.user_interface, context.resource_manager);
+                            }
+                            PhysicalKey::Code(KeyCode::Escape) => {
+                                if let Some(backlog) = self.backlog.take() {
+                                    backlog.remove(context.user_interface);
+                                }
+                            }
+                            PhysicalKey::Code(KeyCode::F5) => self.save_to_slot(QUICK_SAVE_SLOT),
+                            PhysicalKey::Code(KeyCode::F9) => {
+                                self.load_from_slot(QUICK_SAVE_SLOT, context.user_interface, context.resource_manager);
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                // Scrolling up opens the backlog, once it's open the scroll
+                // viewer takes over.
+                WindowEvent::MouseWheel { delta, .. } => {
+                    let up = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => *y > 0.0,
+                        MouseScrollDelta::PixelDelta(position) => position.y > 0.0,
+                    };
+                    if up && self.backlog.is_none() {
+                        self.open_backlog(context.user_interface, context.resource_manager);
+                    }
+                }
                 _ => {}
             }
         }
@@ -146,16 +671,53 @@ impl Plugin for Game {
     ) {
         // Simple example of message system. We'll catch "Click" messages from the button
         // and send new message to the button that will contain new position for it.
+        if let Some(TextMessage::Text(text)) = message.data::<TextMessage>() {
+            if let Some(prompt) = self.prompt.as_mut() {
+                if message.destination() == prompt.text_box && message.direction() == MessageDirection::FromWidget {
+                    prompt.value = text.clone();
+                }
+            }
+        }
         if let Some(ButtonMessage::Click) = message.data::<ButtonMessage>() {
-            if message.destination() == self.button {
+            if self.prompt.as_ref().is_some_and(|prompt| prompt.ok == message.destination()) {
+                let value = self.prompt.as_ref().map(|prompt| prompt.value.trim().to_string()).unwrap_or_default();
+                if value.is_empty() {
+                    if let Some(prompt) = self.prompt.as_ref() {
+                        prompt.show_error(context.user_interface, "Please enter something first.");
+                    }
+                } else {
+                    self.apply_input(context, DialogueInput::SubmitText(value));
+                }
+            } else if message.destination() == self.button {
                 println!("pressed!");
                 // Generate random position in the window.
                 if let GraphicsContext::Initialized(ref _graphics_context) = context.graphics_context {
-                    let ui = &context.user_interface;
-                    let start = ScriptPos { script: "Pharaoh1".to_string(), index: 0 };
-                    let script_pos= self.current_script_pos.as_ref().unwrap_or(&start);
-                    process_script(script_pos, &self.scripts, ui, self.text);
+                    if self.scripts_failed {
+                        Log::err("scripts failed to load, see above.");
+                    } else if !self.scripts_loaded {
+                        Log::warn("scripts are still loading.");
+                    } else if self.runner.is_finished() {
+                        self.runner.start(&self.scripts, START_SCRIPT);
+                        self.handle_events(context.user_interface, context.resource_manager);
+                        self.sync_ui(context.user_interface, context.resource_manager);
+                    } else if self.dialogue_revealing(context.user_interface) {
+                        // The first click shows the rest of the line, the
+                        // second one moves on.
+                        context.user_interface.send_message(better_text::TextMessage::reveal_all(
+                            self.dialogue_text,
+                            MessageDirection::ToWidget,
+                        ));
+                    } else {
+                        self.apply_input(context, DialogueInput::Continue);
+                    }
                 }
+            } else if let Some(index) = self.choice_menu.as_ref().and_then(|menu| menu.index_of(message.destination())) {
+                self.apply_input(context, DialogueInput::Choose(index));
+            } else if let Some(item) = self.topic_menu.as_ref().and_then(|menu| menu.item_of(message.destination())) {
+                let item = item.to_string();
+                self.apply_input(context, DialogueInput::PickTopic(item));
+            } else if self.topic_menu.as_ref().is_some_and(|menu| menu.is_leave(message.destination())) {
+                self.apply_input(context, DialogueInput::LeaveTopics);
             }
         }
     }
@@ -288,60 +850,3 @@ fn create_stack_panel(
     )
     .build(ctx)
 }
-
-pub fn process_script(script_pos: &ScriptPos, scripts: &script::Scripts, ui: &&mut fyrox::gui::UserInterface, dialogue_box:Handle<UiNode>) -> Option<ScriptPos>{
-    let mut current_script = script_pos.script.clone();
-    let mut next_index = script_pos.index;
-    loop {
-        let Some(item) =scripts.get_item(&current_script, next_index) else {
-            Log::info(format!("Ran off the end of {} as {}", &script_pos.script, &script_pos.index));
-            return None;
-        };
-        next_index += 1;
-        match item {
-            script::ScriptItem::Action(_blurp) => {
-                
-            }
-            script::ScriptItem::AddQuest(_blurp) => {
-                
-            }
-            script::ScriptItem::Animation(_blurp) => {
-                
-            }
-            script::ScriptItem::Blurp(blurp) => {
-                show_dialogue(ui, dialogue_box, blurp.text.clone());
-                break;
-            }
-            script::ScriptItem::Choice(_blurp) => {
-                
-            }
-            script::ScriptItem::Cue(_blurp) => {
-                
-            }
-            script::ScriptItem::End(_blurp) => {
-                
-            }
-            script::ScriptItem::Jump(jump) => {
-                if scripts.has_script(&jump.jump) {
-                    Log::info(format!(
-                        "jumping to script {} from script {} @{}.", 
-                        current_script, 
-                        jump.jump, 
-                        next_index - 1
-                    ));
-                    current_script = jump.jump.clone();
-                    next_index = 0;
-                } else {
-                    Log::err(format!("tried to jump to non existant script {}.", jump.jump));
-                    return None;
-                }
-            }
-            script::ScriptItem::OfferTopics(_blurp) => {
-               
-            }
-        }
-    }
-    Some(ScriptPos { script: current_script, index: next_index })
- 
-}
-
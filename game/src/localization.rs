@@ -0,0 +1,592 @@
+//! Translations of dialogue text through string tables.
+//!
+//! Every blurp and choice option text gets a string id, the `id` written on
+//! it or else one made from where it is, `Script.3` for the blurp at index 3
+//! and `Script.3.1` for the second option of the choice there. Positional ids
+//! shift when items are inserted above them, so text that's already being
+//! translated should get an `id` of its own.
+//!
+//! A string table is a PO or CSV file per locale with the source text each
+//! translation was made from. When the source text changes the translation
+//! is stale and the source text is shown until a translator has looked at it
+//! again, the same as for strings missing from the table.
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use crate::{
+    script::{Blurp, ChoiceOption, ScriptItem, Scripts},
+    validate::{Issue, Severity},
+};
+
+pub const PO_EXTENSION: &str = "po";
+pub const CSV_EXTENSION: &str = "csv";
+/// Where the game looks for `<locale>.po`, or `<locale>.csv` when there is none.
+pub const LOCALE_DIR: &str = "data/locale";
+
+pub fn blurp_id(script: &str, index: usize, blurp: &Blurp) -> String {
+    blurp.id.clone().unwrap_or_else(|| format!("{}.{}", script, index))
+}
+
+pub fn option_id(script: &str, index: usize, option_index: usize, option: &ChoiceOption) -> String {
+    option
+        .id
+        .clone()
+        .unwrap_or_else(|| format!("{}.{}.{}", script, index, option_index))
+}
+
+/// A translatable string of the scripts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceString {
+    pub id: String,
+    pub text: String,
+    pub script: String,
+    pub index: usize,
+    /// Who says it, or that it's an option, for the translators.
+    pub context: String,
+}
+
+/// Every translatable string, sorted by script and index.
+pub fn source_strings(scripts: &Scripts) -> Vec<SourceString> {
+    let mut names: Vec<&String> = scripts.keys().collect();
+    names.sort();
+    let mut strings = Vec::new();
+    for name in names {
+        for (index, item) in scripts.get(name).into_iter().flatten().enumerate() {
+            match item {
+                ScriptItem::Blurp(blurp) => strings.push(SourceString {
+                    id: blurp_id(name, index, blurp),
+                    text: blurp.text.clone(),
+                    script: name.clone(),
+                    index,
+                    context: blurp.character.clone(),
+                }),
+                ScriptItem::Choice(choice) => {
+                    for (option_index, option) in choice.choice.iter().enumerate() {
+                        strings.push(SourceString {
+                            id: option_id(name, index, option_index, option),
+                            text: option.text.clone(),
+                            script: name.clone(),
+                            index,
+                            context: "choice".to_string(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    strings
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TableEntry {
+    /// The text the translation was made from.
+    pub source: String,
+    pub translation: String,
+    /// Set when the source text changed since, until a translator clears it.
+    pub fuzzy: bool,
+    pub context: String,
+}
+
+impl TableEntry {
+    pub fn is_translated(&self) -> bool {
+        !self.translation.is_empty()
+    }
+}
+
+/// One locale's translations by string id.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StringTable {
+    entries: BTreeMap<String, TableEntry>,
+}
+
+impl StringTable {
+    pub fn get(&self, id: &str) -> Option<&TableEntry> {
+        self.entries.get(id)
+    }
+
+    pub fn insert(&mut self, id: String, entry: TableEntry) -> Option<TableEntry> {
+        self.entries.insert(id, entry)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &TableEntry)> {
+        self.entries.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The translation of `source`, or `source` itself when there's none or
+    /// it was made from different text.
+    pub fn translate<'a>(&'a self, id: &str, source: &'a str) -> &'a str {
+        match self.entries.get(id) {
+            Some(entry) if entry.is_translated() && !entry.fuzzy && entry.source == source => {
+                &entry.translation
+            }
+            _ => source,
+        }
+    }
+
+    /// Reads a table in whichever format `file`'s extension says.
+    pub fn parse(data: &str, file: &Path) -> Result<Self, String> {
+        if file.extension().is_some_and(|extension| extension == CSV_EXTENSION) {
+            parse_csv(data)
+        } else {
+            parse_po(data)
+        }
+    }
+
+    /// Writes the table in whichever format `file`'s extension says.
+    pub fn write(&self, file: &Path) -> String {
+        if file.extension().is_some_and(|extension| extension == CSV_EXTENSION) {
+            self.to_csv()
+        } else {
+            self.to_po()
+        }
+    }
+
+    pub fn to_po(&self) -> String {
+        let mut out = String::from("msgid \"\"\nmsgstr \"Content-Type: text/plain; charset=UTF-8\\n\"\n");
+        for (id, entry) in &self.entries {
+            out.push('\n');
+            if !entry.context.is_empty() {
+                let _ = writeln!(out, "#. {}", entry.context);
+            }
+            if entry.fuzzy {
+                out.push_str("#, fuzzy\n");
+            }
+            let _ = writeln!(out, "msgctxt {}", po_quote(id));
+            let _ = writeln!(out, "msgid {}", po_quote(&entry.source));
+            let _ = writeln!(out, "msgstr {}", po_quote(&entry.translation));
+        }
+        out
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("id,context,source,translation,fuzzy\n");
+        for (id, entry) in &self.entries {
+            let fields = [
+                id.as_str(),
+                &entry.context,
+                &entry.source,
+                &entry.translation,
+                if entry.fuzzy { "fuzzy" } else { "" },
+            ];
+            let line: Vec<String> = fields.iter().map(|field| csv_quote(field)).collect();
+            let _ = writeln!(out, "{}", line.join(","));
+        }
+        out
+    }
+}
+
+fn po_quote(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn po_unquote(text: &str, line: usize) -> Result<String, String> {
+    let inner = text
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+        .ok_or_else(|| format!("line {}: expected a quoted string", line))?;
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some(c @ ('"' | '\\')) => out.push(c),
+            other => return Err(format!("line {}: unknown escape \\{}", line, other.unwrap_or(' '))),
+        }
+    }
+    Ok(out)
+}
+
+pub fn parse_po(data: &str) -> Result<StringTable, String> {
+    #[derive(Default)]
+    struct Pending {
+        context: String,
+        fuzzy: bool,
+        id: Option<String>,
+        source: Option<String>,
+        translation: Option<String>,
+    }
+    fn finish(table: &mut StringTable, pending: Pending) -> Result<(), String> {
+        let (Some(source), Some(translation)) = (pending.source, pending.translation) else {
+            return Ok(());
+        };
+        // The header has no context and an empty msgid.
+        let Some(id) = pending.id else {
+            return Ok(());
+        };
+        let entry = TableEntry {
+            source,
+            translation,
+            fuzzy: pending.fuzzy,
+            context: pending.context,
+        };
+        match table.insert(id.clone(), entry) {
+            Some(_) => Err(format!("string {} is in the table twice", id)),
+            None => Ok(()),
+        }
+    }
+
+    let mut table = StringTable::default();
+    let mut pending = Pending::default();
+    // Which string continuation lines add to.
+    let mut field: Option<&str> = None;
+    for (index, line) in data.lines().enumerate() {
+        let number = index + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            if pending.translation.is_some() {
+                finish(&mut table, std::mem::take(&mut pending))?;
+            }
+            field = None;
+            if let Some(context) = comment.strip_prefix(". ") {
+                pending.context = context.to_string();
+            } else if let Some(flags) = comment.strip_prefix(", ") {
+                pending.fuzzy |= flags.split(',').any(|flag| flag.trim() == "fuzzy");
+            }
+            continue;
+        }
+        if line.starts_with('"') {
+            let text = po_unquote(line, number)?;
+            let target = match field {
+                Some("msgctxt") => pending.id.as_mut(),
+                Some("msgid") => pending.source.as_mut(),
+                Some("msgstr") => pending.translation.as_mut(),
+                _ => None,
+            };
+            target
+                .ok_or_else(|| format!("line {}: string outside of an entry", number))?
+                .push_str(&text);
+            continue;
+        }
+        let (keyword, value) = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| format!("line {}: expected a keyword and a string", number))?;
+        if (keyword == "msgctxt" || keyword == "msgid") && pending.translation.is_some() {
+            finish(&mut table, std::mem::take(&mut pending))?;
+        }
+        let value = po_unquote(value.trim(), number)?;
+        match keyword {
+            "msgctxt" => pending.id = Some(value),
+            "msgid" => pending.source = Some(value),
+            "msgstr" => pending.translation = Some(value),
+            _ => return Err(format!("line {}: unsupported keyword {}", number, keyword)),
+        }
+        field = Some(match keyword {
+            "msgctxt" => "msgctxt",
+            "msgid" => "msgid",
+            _ => "msgstr",
+        });
+    }
+    finish(&mut table, pending)?;
+    Ok(table)
+}
+
+fn csv_quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) || field != field.trim() {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Splits CSV into records of fields, quoted fields may span lines.
+fn csv_records(data: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = data.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            line += 1;
+        }
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(format!("line {}: quoted field is missing its closing \"", line));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+pub fn parse_csv(data: &str) -> Result<StringTable, String> {
+    let records = csv_records(data)?;
+    let mut records = records.into_iter();
+    let header = records.next().ok_or("the table is empty")?;
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|column| column == name)
+            .ok_or_else(|| format!("the table has no {} column", name))
+    };
+    let (id, source, translation) = (column("id")?, column("source")?, column("translation")?);
+    let (context, fuzzy) = (column("context").ok(), column("fuzzy").ok());
+    let mut table = StringTable::default();
+    for record in records {
+        if record.iter().all(|field| field.is_empty()) {
+            continue;
+        }
+        let get = |index: Option<usize>| {
+            index
+                .and_then(|index| record.get(index))
+                .cloned()
+                .unwrap_or_default()
+        };
+        let entry = TableEntry {
+            source: get(Some(source)),
+            translation: get(Some(translation)),
+            fuzzy: !get(fuzzy).trim().is_empty(),
+            context: get(context),
+        };
+        let id = get(Some(id));
+        if table.insert(id.clone(), entry).is_some() {
+            return Err(format!("string {} is in the table twice", id));
+        }
+    }
+    Ok(table)
+}
+
+/// A table with every string of `scripts`, keeping the translations of
+/// `previous`. Entries whose source text changed are marked fuzzy and ones
+/// that are gone are dropped.
+pub fn extract(scripts: &Scripts, previous: Option<&StringTable>) -> StringTable {
+    let mut table = StringTable::default();
+    for string in source_strings(scripts) {
+        let mut entry = previous
+            .and_then(|previous| previous.get(&string.id))
+            .cloned()
+            .unwrap_or_default();
+        if entry.source != string.text {
+            entry.fuzzy |= entry.is_translated();
+            entry.source = string.text;
+        }
+        entry.context = string.context;
+        table.insert(string.id, entry);
+    }
+    table
+}
+
+/// Ids used by more than one string.
+fn duplicate_ids(strings: &[SourceString]) -> Vec<Issue> {
+    let mut first: BTreeMap<&str, &SourceString> = BTreeMap::new();
+    let mut issues = Vec::new();
+    for string in strings {
+        match first.get(string.id.as_str()) {
+            Some(other) => issues.push(Issue {
+                severity: Severity::Error,
+                script: string.script.clone(),
+                index: Some(string.index),
+                message: format!(
+                    "string id {} is already used by {} @{}",
+                    string.id, other.script, other.index
+                ),
+                location: None,
+            }),
+            None => {
+                first.insert(&string.id, string);
+            }
+        }
+    }
+    issues
+}
+
+/// Reports strings of `scripts` that `table` has no translation for, or one
+/// made from different text, and entries for strings that are gone.
+pub fn check_translations(scripts: &Scripts, table: &StringTable) -> Vec<Issue> {
+    let strings = source_strings(scripts);
+    let mut issues = duplicate_ids(&strings);
+    let issue = |string: &SourceString, message: String| Issue {
+        severity: Severity::Warning,
+        script: string.script.clone(),
+        index: Some(string.index),
+        message,
+        location: scripts.location(&string.script, string.index).cloned(),
+    };
+    for string in &strings {
+        match table.get(&string.id) {
+            Some(entry) if !entry.is_translated() => {
+                issues.push(issue(string, format!("string {} isn't translated", string.id)))
+            }
+            None => issues.push(issue(string, format!("string {} isn't in the table", string.id))),
+            Some(entry) if entry.source != string.text => issues.push(issue(
+                string,
+                format!("string {} was translated from different text: {:?}", string.id, entry.source),
+            )),
+            Some(entry) if entry.fuzzy => issues.push(issue(
+                string,
+                format!("string {} is marked fuzzy, its text changed since it was translated", string.id),
+            )),
+            Some(_) => {}
+        }
+    }
+    for (id, _) in table.iter() {
+        if !strings.iter().any(|string| &string.id == id) {
+            issues.push(Issue {
+                severity: Severity::Warning,
+                script: id.clone(),
+                index: None,
+                message: "string is in the table but not in the scripts".to_string(),
+                location: None,
+            });
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::screenplay::parse_screenplay;
+
+    fn table() -> StringTable {
+        let mut table = StringTable::default();
+        table.insert(
+            "Start.0".to_string(),
+            TableEntry {
+                source: "Hello, \"friend\".\nHow are you?".to_string(),
+                translation: "Hallo, \"Freund\".\nWie geht's?".to_string(),
+                fuzzy: false,
+                context: "Narrator".to_string(),
+            },
+        );
+        table.insert(
+            "Start.1.0".to_string(),
+            TableEntry {
+                source: "Fine, thanks\\".to_string(),
+                translation: " Gut,\tdanke ".to_string(),
+                fuzzy: true,
+                context: "choice".to_string(),
+            },
+        );
+        table.insert(
+            "Start.2".to_string(),
+            TableEntry {
+                source: "Not yet.".to_string(),
+                ..TableEntry::default()
+            },
+        );
+        table
+    }
+
+    #[test]
+    fn po_round_trip() {
+        let table = table();
+        assert_eq!(parse_po(&table.to_po()), Ok(table));
+    }
+
+    #[test]
+    fn po_continuation_lines() {
+        let data = "msgid \"\"\nmsgstr \"\"\n\"Content-Type: text/plain\\n\"\n\n#, fuzzy\nmsgctxt \"a\"\nmsgid \"\"\n\"one \"\n\"two\"\nmsgstr \"eins zwei\"\n";
+        let table = parse_po(data).unwrap();
+        let entry = table.get("a").unwrap();
+        assert_eq!(entry.source, "one two");
+        assert_eq!(entry.translation, "eins zwei");
+        assert!(entry.fuzzy);
+        assert_eq!(table.iter().count(), 1);
+    }
+
+    #[test]
+    fn po_errors() {
+        assert!(parse_po("msgctxt \"a\"\nmsgid \"x\"\nmsgstr \"y\"\nmsgctxt \"a\"\nmsgid \"x\"\nmsgstr \"y\"\n").is_err());
+        assert_eq!(parse_po("msgid \"\\q\""), Err("line 1: unknown escape \\q".to_string()));
+        assert_eq!(parse_po("msgid x"), Err("line 1: expected a quoted string".to_string()));
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let table = table();
+        assert_eq!(parse_csv(&table.to_csv()), Ok(table));
+    }
+
+    #[test]
+    fn csv_columns_in_any_order() {
+        let table = parse_csv("translation,id,source\r\nHallo,Start.0,Hello\r\n,,\r\n").unwrap();
+        assert_eq!(
+            table.get("Start.0"),
+            Some(&TableEntry {
+                source: "Hello".to_string(),
+                translation: "Hallo".to_string(),
+                ..TableEntry::default()
+            })
+        );
+        assert_eq!(table.iter().count(), 1);
+        assert_eq!(parse_csv("id,source\n"), Err("the table has no translation column".to_string()));
+        assert!(parse_csv("id,source,translation\na,\"b,c\n").is_err());
+    }
+
+    #[test]
+    fn extract_marks_changed_text_fuzzy() {
+        let old = parse_screenplay(
+            "== Start ==\nNarrator: Hello.\nNarrator: Bye.\nNarrator: Gone.\n",
+            Path::new("old.screenplay"),
+        )
+        .unwrap();
+        let mut previous = extract(&old, None);
+        for id in ["Start.0", "Start.1"] {
+            let mut entry = previous.get(id).unwrap().clone();
+            entry.translation = format!("{} translated", id);
+            previous.insert(id.to_string(), entry);
+        }
+        let new = parse_screenplay(
+            "== Start ==\nNarrator: Hello.\nNarrator: Goodbye.\n",
+            Path::new("new.screenplay"),
+        )
+        .unwrap();
+        let table = extract(&new, Some(&previous));
+
+        let kept = table.get("Start.0").unwrap();
+        assert!(!kept.fuzzy);
+        assert_eq!(table.translate("Start.0", "Hello."), "Start.0 translated");
+
+        let changed = table.get("Start.1").unwrap();
+        assert!(changed.fuzzy);
+        assert_eq!(changed.source, "Goodbye.");
+        assert_eq!(changed.translation, "Start.1 translated");
+        assert_eq!(table.translate("Start.1", "Goodbye."), "Goodbye.");
+
+        assert_eq!(table.get("Start.2"), None);
+    }
+}
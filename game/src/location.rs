@@ -0,0 +1,335 @@
+//! Where in the scripts files things are, so problems can point at the JSON
+//! to fix.
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use fyrox::core::{
+    reflect::prelude::*,
+    visitor::{Visit, VisitResult, Visitor},
+};
+
+/// A position in a scripts file, `line` and `column` start at 1.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Visit, Reflect)]
+pub struct Location {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    pub fn new(file: &Path, line: usize, column: usize) -> Self {
+        Self {
+            file: file.to_path_buf(),
+            line,
+            column,
+        }
+    }
+
+    /// Somewhere in `file`, when nothing more precise is known.
+    pub fn file(file: &Path) -> Self {
+        Self::new(file, 0, 0)
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.file.display())
+        } else {
+            write!(f, "{}:{}:{}", self.file.display(), self.line, self.column)
+        }
+    }
+}
+
+/// Where a script's name and each of its items start.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScriptLocations {
+    pub script: Location,
+    pub items: Vec<Location>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptError {
+    /// The file couldn't be read.
+    Io { file: PathBuf, message: String },
+    /// The file isn't valid scripts JSON.
+    Parse { location: Location, message: String },
+    /// Two files of a manifest define the same script.
+    DuplicateScript {
+        script: String,
+        first: Location,
+        second: Location,
+    },
+}
+
+impl ScriptError {
+    pub(crate) fn from_json(err: serde_jsonrc::Error, file: &Path) -> Self {
+        let position = format!(" at line {} column {}", err.line(), err.column());
+        let message = err.to_string();
+        let message = message.strip_suffix(&position).unwrap_or(&message).to_string();
+        ScriptError::Parse {
+            location: Location::new(file, err.line(), err.column()),
+            message,
+        }
+    }
+
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            ScriptError::Io { .. } => None,
+            ScriptError::Parse { location, .. } => Some(location),
+            ScriptError::DuplicateScript { second, .. } => Some(second),
+        }
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Io { file, message } => {
+                write!(f, "{}: couldn't read: {}", file.display(), message)
+            }
+            ScriptError::Parse { location, message } => write!(f, "{}: {}", location, message),
+            ScriptError::DuplicateScript {
+                script,
+                first,
+                second,
+            } => write!(
+                f,
+                "{}: script {} is already defined at {}",
+                second, script, first
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+#[derive(Debug, PartialEq)]
+enum TokenKind {
+    Open,
+    Close,
+    Colon,
+    Comma,
+    Str(String),
+    Other,
+}
+
+struct Token {
+    kind: TokenKind,
+    line: usize,
+    column: usize,
+}
+
+fn advance(c: char, line: &mut usize, column: &mut usize) {
+    if c == '\n' {
+        *line += 1;
+        *column = 1;
+    } else {
+        *column += 1;
+    }
+}
+
+/// Splits JSON, comments and trailing commas included, into just enough
+/// tokens to follow its structure.
+fn tokenize(data: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = data.chars().peekable();
+    let (mut line, mut column) = (1, 1);
+    while let Some(c) = chars.next() {
+        let (start_line, start_column) = (line, column);
+        advance(c, &mut line, &mut column);
+        let kind = match c {
+            '{' | '[' => TokenKind::Open,
+            '}' | ']' => TokenKind::Close,
+            ':' => TokenKind::Colon,
+            ',' => TokenKind::Comma,
+            '"' => {
+                let mut text = String::new();
+                while let Some(c) = chars.next() {
+                    advance(c, &mut line, &mut column);
+                    match c {
+                        '"' => break,
+                        '\\' => {
+                            if let Some(escaped) = chars.next() {
+                                advance(escaped, &mut line, &mut column);
+                                text.push(escaped);
+                            }
+                        }
+                        c => text.push(c),
+                    }
+                }
+                TokenKind::Str(text)
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    advance(c, &mut line, &mut column);
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                continue;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    advance(c, &mut line, &mut column);
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            _ => {
+                while chars
+                    .peek()
+                    .is_some_and(|c| !c.is_whitespace() && !"{}[]:,\"/".contains(*c))
+                {
+                    let c = chars.next().unwrap();
+                    advance(c, &mut line, &mut column);
+                }
+                TokenKind::Other
+            }
+        };
+        tokens.push(Token {
+            kind,
+            line: start_line,
+            column: start_column,
+        });
+    }
+    tokens
+}
+
+/// Index of the token after the value starting at `index`.
+fn skip_value(tokens: &[Token], index: usize) -> usize {
+    if tokens.get(index).map(|token| &token.kind) != Some(&TokenKind::Open) {
+        return index + 1;
+    }
+    let mut depth = 0;
+    for (offset, token) in tokens[index..].iter().enumerate() {
+        match token.kind {
+            TokenKind::Open => depth += 1,
+            TokenKind::Close => {
+                depth -= 1;
+                if depth == 0 {
+                    return index + offset + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens.len()
+}
+
+/// Finds where every script and item in a scripts file starts. Only meant for
+/// files that already parsed, anything it doesn't understand is skipped.
+pub fn scan_locations(data: &str, file: &Path) -> HashMap<String, ScriptLocations> {
+    let tokens = tokenize(data);
+    let location = |token: &Token| Location::new(file, token.line, token.column);
+    let mut found = HashMap::new();
+    if tokens.first().map(|token| &token.kind) != Some(&TokenKind::Open) {
+        return found;
+    }
+    let mut index = 1;
+    while let Some(token) = tokens.get(index) {
+        let name = match &token.kind {
+            TokenKind::Str(name) => name.clone(),
+            TokenKind::Comma => {
+                index += 1;
+                continue;
+            }
+            _ => break,
+        };
+        let mut script = ScriptLocations {
+            script: location(token),
+            items: Vec::new(),
+        };
+        // Skip the name and the colon.
+        index += 2;
+        if tokens.get(index).map(|token| &token.kind) == Some(&TokenKind::Open) {
+            index += 1;
+            while let Some(token) = tokens.get(index) {
+                match token.kind {
+                    TokenKind::Close => {
+                        index += 1;
+                        break;
+                    }
+                    TokenKind::Comma => index += 1,
+                    _ => {
+                        script.items.push(location(token));
+                        index = skip_value(&tokens, index);
+                    }
+                }
+            }
+        } else {
+            index = skip_value(&tokens, index);
+        }
+        found.insert(name, script);
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPTS: &str = r#"{
+  "Say \"hi\"": [
+    {"blurp": "a \"quoted\" [line] {with} \\", "character": "A"},
+    {"choice": [{"text": "x", "next": [1, [2, {}]]}]}
+  ],
+  // "Commented": [{}],
+  /* "Also": [{}], */ "Other": [ {"end": ""} ],
+  "Empty": []
+}"#;
+
+    fn scan(data: &str) -> HashMap<String, ScriptLocations> {
+        scan_locations(data, Path::new("test.dialogue"))
+    }
+
+    fn at(line: usize, column: usize) -> Location {
+        Location::new(Path::new("test.dialogue"), line, column)
+    }
+
+    #[test]
+    fn scripts_and_items() {
+        let found = scan(SCRIPTS);
+        assert_eq!(found.len(), 3);
+        assert_eq!(
+            found["Say \"hi\""],
+            ScriptLocations {
+                script: at(2, 3),
+                items: vec![at(3, 5), at(4, 5)],
+            }
+        );
+        assert_eq!(
+            found["Other"],
+            ScriptLocations {
+                script: at(7, 23),
+                items: vec![at(7, 34)],
+            }
+        );
+        assert_eq!(
+            found["Empty"],
+            ScriptLocations {
+                script: at(8, 3),
+                items: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn crlf() {
+        assert_eq!(scan(&SCRIPTS.replace('\n', "\r\n")), scan(SCRIPTS));
+    }
+
+    #[test]
+    fn not_an_object() {
+        assert!(scan("\"Start\"").is_empty());
+        assert!(scan("").is_empty());
+    }
+}
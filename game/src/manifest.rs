@@ -0,0 +1,239 @@
+//! Script projects spread over several files.
+//!
+//! A manifest is a small JSON file listing the scripts files to load, relative
+//! to the manifest:
+//!
+//! ```json
+//! { "include": ["scripts.dialogue", "quests/*.dialogue"] }
+//! ```
+//!
+//! `*` and `?` may be used in the file name part of an entry. Patterns need a
+//! file system to look in, so the browser build has to list files one by one.
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    location::{Location, ScriptError},
+    script::Scripts,
+};
+
+pub const MANIFEST_EXTENSION: &str = "manifest";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub include: Vec<String>,
+}
+
+impl Manifest {
+    pub fn parse(data: &str) -> Result<Self, String> {
+        serde_jsonrc::from_str(data).map_err(|err| err.to_string())
+    }
+
+    /// The files the manifest lists, in order and each only once. `dir` is the
+    /// directory the manifest is in.
+    pub fn files(&self, dir: &Path) -> Result<Vec<PathBuf>, String> {
+        let mut files = Vec::new();
+        for entry in &self.include {
+            let path = dir.join(entry);
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| format!("{} doesn't name a file", entry))?;
+            if !name.contains(['*', '?']) {
+                files.push(path);
+                continue;
+            }
+            let parent = path.parent().unwrap_or(dir);
+            let listing = fs::read_dir(parent)
+                .map_err(|err| format!("can't look for {} in {}: {}", name, parent.display(), err))?;
+            let mut matched: Vec<PathBuf> = listing
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+                .filter(|entry| entry.file_name().to_str().is_some_and(|file| matches(name, file)))
+                .map(|entry| parent.join(entry.file_name()))
+                .collect();
+            // Directory order differs between systems, sort so the merged
+            // scripts don't depend on it.
+            matched.sort();
+            if matched.is_empty() {
+                return Err(format!("{} doesn't match any files", entry));
+            }
+            files.extend(matched);
+        }
+        let mut seen = Vec::new();
+        files.retain(|file| {
+            let new = !seen.contains(file);
+            seen.push(file.clone());
+            new
+        });
+        Ok(files)
+    }
+}
+
+/// The scripts files `path` stands for, itself unless it's a manifest.
+pub fn script_files(path: &Path) -> Result<Vec<PathBuf>, String> {
+    if path.extension().is_some_and(|extension| extension == MANIFEST_EXTENSION) {
+        let data = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        Manifest::parse(&data)?.files(dir)
+    } else {
+        Ok(vec![path.to_path_buf()])
+    }
+}
+
+/// Matches `name` against a pattern where `*` is any run of characters and `?`
+/// any single one.
+fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // Let the last `*` swallow one more character and try again.
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Which file each script was loaded from.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScriptSources {
+    files: HashMap<String, PathBuf>,
+}
+
+impl ScriptSources {
+    pub fn file(&self, script: &str) -> Option<&Path> {
+        self.files.get(script).map(PathBuf::as_path)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &PathBuf)> {
+        self.files.iter()
+    }
+}
+
+/// Merges the scripts of several files into one collection.
+pub fn merge<'a>(
+    files: impl IntoIterator<Item = (&'a Path, &'a Scripts)>,
+) -> Result<(Scripts, ScriptSources), ScriptError> {
+    let mut merged = Scripts::default();
+    let mut sources = ScriptSources::default();
+    for (file, scripts) in files {
+        for (name, items) in scripts.iter() {
+            if let Some(first) = sources.files.get(name) {
+                return Err(ScriptError::DuplicateScript {
+                    script: name.clone(),
+                    first: merged
+                        .locations(name)
+                        .map_or_else(|| Location::file(first), |locations| locations.script.clone()),
+                    second: scripts
+                        .locations(name)
+                        .map_or_else(|| Location::file(file), |locations| locations.script.clone()),
+                });
+            }
+            sources.files.insert(name.clone(), file.to_path_buf());
+            merged.insert(name.clone(), items.clone());
+            if let Some(locations) = scripts.locations(name) {
+                merged.set_locations(name, locations.clone());
+            }
+        }
+    }
+    Ok((merged, sources))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::parse_scripts;
+
+    #[test]
+    fn patterns() {
+        assert!(matches("*.dialogue", "intro.dialogue"));
+        assert!(matches("*.dialogue", ".dialogue"));
+        assert!(!matches("*.dialogue", "intro.dialogue.bak"));
+        assert!(matches("act?.dialogue", "act1.dialogue"));
+        assert!(!matches("act?.dialogue", "act.dialogue"));
+        assert!(!matches("act?.dialogue", "act12.dialogue"));
+        assert!(matches("a*b*c", "aXbYbZc"));
+        assert!(!matches("a*b*c", "aXbYbZ"));
+        assert!(matches("*", ""));
+        assert!(matches("**x", "x"));
+    }
+
+    #[test]
+    fn files() {
+        let dir = std::env::temp_dir().join(format!("manifest-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("quests")).unwrap();
+        for file in ["b.dialogue", "a.dialogue", "notes.txt"] {
+            fs::write(dir.join("quests").join(file), "{}").unwrap();
+        }
+        let manifest = Manifest::parse(
+            r#"{ "include": ["main.dialogue", "quests/*.dialogue", "quests/?.dialogue"] }"#,
+        )
+        .unwrap();
+        let files = manifest.files(&dir);
+        let missing = Manifest::parse(r#"{ "include": ["quests/*.screenplay"] }"#)
+            .unwrap()
+            .files(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        // Listed files don't have to exist yet, matches are sorted and only
+        // come once.
+        assert_eq!(
+            files,
+            Ok(vec![
+                dir.join("main.dialogue"),
+                dir.join("quests").join("a.dialogue"),
+                dir.join("quests").join("b.dialogue"),
+            ])
+        );
+        assert_eq!(missing, Err("quests/*.screenplay doesn't match any files".to_string()));
+    }
+
+    #[test]
+    fn duplicate_scripts() {
+        let first_file = Path::new("first.dialogue");
+        let second_file = Path::new("second.dialogue");
+        let first = parse_scripts("{\"Start\": [{\"end\": \"\"}]}", first_file).unwrap();
+        let second = parse_scripts("{\n  \"Other\": [],\n  \"Start\": []\n}", second_file).unwrap();
+        let err = merge([(first_file, &first), (second_file, &second)]).unwrap_err();
+        assert_eq!(
+            err,
+            ScriptError::DuplicateScript {
+                script: "Start".to_string(),
+                first: Location::new(first_file, 1, 2),
+                second: Location::new(second_file, 3, 3),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "second.dialogue:3:3: script Start is already defined at first.dialogue:1:2"
+        );
+
+        let (merged, sources) = merge([(first_file, &first)]).unwrap();
+        assert!(merged.has_script("Start"));
+        assert_eq!(sources.file("Start"), Some(first_file));
+    }
+}
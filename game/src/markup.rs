@@ -0,0 +1,452 @@
+//! Tags inside dialogue text, read by the text widget in `better_text`.
+//!
+//! They're angle brackets so they don't clash with the `[Name]` placeholders,
+//! which are filled in first, with `escape_markup` on the values. Styles nest
+//! and are closed with `</tag>`:
+//!
+//! - `<b>` and `<i>` switch to the bold and italic fonts.
+//! - `<em>` is the speaker's emphasis, drawn the way the widget is told to.
+//! - `<color=#rrggbb>` (also `#rgb` and `#rrggbbaa`) colours the text.
+//! - `<size=24>` sets the font size in pixels, `<size=150%>` scales it.
+//!
+//! Effects animate each glyph on its own, for the lines that need drama:
+//!
+//! - `<shake>` jitters the glyphs, `<shake=4>` by up to 4 pixels.
+//! - `<wave>` bobs them up and down, `<wave=6>` 6 pixels each way.
+//! - `<rainbow>` cycles through the colours, over any `<color>`.
+//! - `<fade>` fades each glyph in as the reveal gets to it.
+//!
+//! `<icon=data/icons/seal.png>` puts a picture in the line, as tall as the
+//! text around it and sitting on its baseline. It wraps along with the word
+//! it's in and takes part in the effects.
+//!
+//! `<pause>` holds the typewriter reveal for `DEFAULT_PAUSE` seconds at that
+//! point and `<pause=1.5>` for as long as given. `<<` stands for a literal
+//! `<`, anything else in angle brackets is shown as it is.
+
+/// Seconds a bare `<pause>` holds the reveal for.
+pub const DEFAULT_PAUSE: f32 = 0.4;
+/// Stands in for an icon in `Markup::text`, so icons take up a character of
+/// the reveal and of wrapping like any letter.
+pub const ICON_CHAR: char = '\u{FFFC}';
+/// Pixels a bare `<shake>` moves glyphs by.
+pub const DEFAULT_SHAKE: f32 = 1.5;
+/// Pixels a bare `<wave>` moves glyphs by.
+pub const DEFAULT_WAVE: f32 = 3.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pause {
+    /// Characters of `Markup::text` shown before the pause.
+    pub at: usize,
+    pub seconds: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Style {
+    pub bold: bool,
+    pub italic: bool,
+    pub emphasis: bool,
+    /// RGBA, the widget's foreground when `None`.
+    pub color: Option<[u8; 4]>,
+    /// Font size in pixels, the widget's own when `None`.
+    pub size: Option<f32>,
+    /// Applied on top of `size`.
+    pub scale: f32,
+    /// How far the glyphs jitter, in pixels.
+    pub shake: Option<f32>,
+    /// How far the glyphs bob, in pixels.
+    pub wave: Option<f32>,
+    pub rainbow: bool,
+    pub fade: bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            bold: false,
+            italic: false,
+            emphasis: false,
+            color: None,
+            size: None,
+            scale: 1.0,
+            shake: None,
+            wave: None,
+            rainbow: false,
+            fade: false,
+        }
+    }
+}
+
+impl Style {
+    /// Whether the glyphs move or change on their own.
+    pub fn is_animated(&self) -> bool {
+        self.shake.is_some() || self.wave.is_some() || self.rainbow || self.fade
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Icon {
+    /// Index of its `ICON_CHAR` in `Markup::text`, in characters.
+    pub at: usize,
+    /// Texture path, as given to the resource manager.
+    pub path: String,
+}
+
+/// Characters `start..end` of `Markup::text` drawn in `style`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub start: usize,
+    pub end: usize,
+    pub style: Style,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Markup {
+    /// The text without its tags.
+    pub text: String,
+    pub pauses: Vec<Pause>,
+    pub icons: Vec<Icon>,
+    /// Cover all of `text` in order, neighbours have different styles.
+    pub runs: Vec<Run>,
+    /// Tags that were left open or closed without being open, and bad
+    /// values, for `validate`.
+    pub problems: Vec<String>,
+}
+
+impl Markup {
+    /// Style of the `index`th character of `text`.
+    pub fn style_at(&self, index: usize) -> Style {
+        self.runs
+            .iter()
+            .find(|run| run.start <= index && index < run.end)
+            .map_or_else(Style::default, |run| run.style)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tag {
+    Bold,
+    Italic,
+    Emphasis,
+    Color([u8; 4]),
+    Size(f32),
+    Scale(f32),
+    Shake(f32),
+    Wave(f32),
+    Rainbow,
+    Fade,
+}
+
+impl Tag {
+    fn name(&self) -> &'static str {
+        match self {
+            Tag::Bold => "b",
+            Tag::Italic => "i",
+            Tag::Emphasis => "em",
+            Tag::Color(_) => "color",
+            Tag::Size(_) | Tag::Scale(_) => "size",
+            Tag::Shake(_) => "shake",
+            Tag::Wave(_) => "wave",
+            Tag::Rainbow => "rainbow",
+            Tag::Fade => "fade",
+        }
+    }
+    fn apply(&self, style: &mut Style) {
+        match *self {
+            Tag::Bold => style.bold = true,
+            Tag::Italic => style.italic = true,
+            Tag::Emphasis => style.emphasis = true,
+            Tag::Color(color) => style.color = Some(color),
+            Tag::Size(size) => {
+                style.size = Some(size);
+                style.scale = 1.0;
+            }
+            Tag::Scale(scale) => style.scale *= scale,
+            Tag::Shake(pixels) => style.shake = Some(pixels),
+            Tag::Wave(pixels) => style.wave = Some(pixels),
+            Tag::Rainbow => style.rainbow = true,
+            Tag::Fade => style.fade = true,
+        }
+    }
+}
+
+enum Parsed {
+    Open(Tag),
+    Close(&'static str),
+    Pause(f32),
+    Icon(String),
+}
+
+const TAG_NAMES: &[&str] = &[
+    "b", "i", "em", "color", "size", "shake", "wave", "rainbow", "fade",
+];
+
+pub fn parse_markup(source: &str) -> Markup {
+    let mut markup = Markup::default();
+    let mut open: Vec<Tag> = Vec::new();
+    let mut style = Style::default();
+    let mut length = 0;
+    let mut rest = source;
+    while let Some(start) = rest.find('<') {
+        push_text(&mut markup, &rest[..start], style, &mut length);
+        let after = &rest[start + 1..];
+        if let Some(after) = after.strip_prefix('<') {
+            push_text(&mut markup, "<", style, &mut length);
+            rest = after;
+            continue;
+        }
+        let parsed = after
+            .find('>')
+            .map(|end| (end, parse_tag(&after[..end], &mut markup.problems)));
+        match parsed {
+            Some((end, Some(Parsed::Pause(seconds)))) => {
+                markup.pauses.push(Pause { at: length, seconds });
+                rest = &after[end + 1..];
+            }
+            Some((end, Some(Parsed::Icon(path)))) => {
+                markup.icons.push(Icon { at: length, path });
+                push_text(&mut markup, &ICON_CHAR.to_string(), style, &mut length);
+                rest = &after[end + 1..];
+            }
+            Some((end, Some(Parsed::Open(tag)))) => {
+                open.push(tag);
+                style = fold(&open);
+                rest = &after[end + 1..];
+            }
+            Some((end, Some(Parsed::Close(name)))) => {
+                match open.iter().rposition(|tag| tag.name() == name) {
+                    Some(index) => {
+                        open.remove(index);
+                        style = fold(&open);
+                    }
+                    None => markup.problems.push(format!("</{}> closes a tag that isn't open", name)),
+                }
+                rest = &after[end + 1..];
+            }
+            _ => {
+                push_text(&mut markup, "<", style, &mut length);
+                rest = after;
+            }
+        }
+    }
+    push_text(&mut markup, rest, style, &mut length);
+    for tag in open {
+        markup.problems.push(format!("<{}> is never closed", tag.name()));
+    }
+    markup
+}
+
+/// `source` as it reads, for places that show dialogue text without the
+/// widget, e.g. the backlog. Icons are left out.
+pub fn plain_text(source: &str) -> String {
+    parse_markup(source)
+        .text
+        .chars()
+        .filter(|c| *c != ICON_CHAR)
+        .collect()
+}
+
+/// `text` with `<` doubled, so it shows as written instead of as tags.
+pub fn escape_markup(text: &str) -> String {
+    text.replace('<', "<<")
+}
+
+fn push_text(markup: &mut Markup, text: &str, style: Style, length: &mut usize) {
+    let count = text.chars().count();
+    if count == 0 {
+        return;
+    }
+    markup.text.push_str(text);
+    match markup.runs.last_mut() {
+        Some(run) if run.style == style => run.end += count,
+        _ => markup.runs.push(Run {
+            start: *length,
+            end: *length + count,
+            style,
+        }),
+    }
+    *length += count;
+}
+
+fn fold(open: &[Tag]) -> Style {
+    let mut style = Style::default();
+    for tag in open {
+        tag.apply(&mut style);
+    }
+    style
+}
+
+/// `None` for text that only looks like a tag, it's shown as it is. Known
+/// tags with bad values are shown as they are too, and reported in
+/// `problems`.
+fn parse_tag(tag: &str, problems: &mut Vec<String>) -> Option<Parsed> {
+    let tag = tag.trim();
+    if let Some(name) = tag.strip_prefix('/') {
+        let name = name.trim();
+        return TAG_NAMES
+            .iter()
+            .find(|known| **known == name)
+            .map(|known| Parsed::Close(known));
+    }
+    let (name, value) = match tag.split_once('=') {
+        Some((name, value)) => (name.trim(), Some(value.trim())),
+        None => (tag, None),
+    };
+    let parsed = match (name, value) {
+        ("pause", None) => Parsed::Pause(DEFAULT_PAUSE),
+        ("pause", Some(value)) => match value.parse::<f32>() {
+            Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => Parsed::Pause(seconds),
+            _ => return bad_value(tag, problems),
+        },
+        ("b", None) => Parsed::Open(Tag::Bold),
+        ("i", None) => Parsed::Open(Tag::Italic),
+        ("em", None) => Parsed::Open(Tag::Emphasis),
+        ("color", Some(value)) => match parse_color(value) {
+            Some(color) => Parsed::Open(Tag::Color(color)),
+            None => return bad_value(tag, problems),
+        },
+        ("size", Some(value)) => {
+            let (number, percent) = match value.strip_suffix('%') {
+                Some(number) => (number.trim(), true),
+                None => (value, false),
+            };
+            match number.parse::<f32>() {
+                Ok(number) if number.is_finite() && number > 0.0 && percent => {
+                    Parsed::Open(Tag::Scale(number / 100.0))
+                }
+                Ok(number) if number.is_finite() && number > 0.0 => Parsed::Open(Tag::Size(number)),
+                _ => return bad_value(tag, problems),
+            }
+        }
+        ("shake", None) => Parsed::Open(Tag::Shake(DEFAULT_SHAKE)),
+        ("shake", Some(value)) => match parse_pixels(value) {
+            Some(pixels) => Parsed::Open(Tag::Shake(pixels)),
+            None => return bad_value(tag, problems),
+        },
+        ("wave", None) => Parsed::Open(Tag::Wave(DEFAULT_WAVE)),
+        ("wave", Some(value)) => match parse_pixels(value) {
+            Some(pixels) => Parsed::Open(Tag::Wave(pixels)),
+            None => return bad_value(tag, problems),
+        },
+        ("icon", Some(path)) if !path.is_empty() => Parsed::Icon(path.to_owned()),
+        ("icon", _) => return bad_value(tag, problems),
+        ("rainbow", None) => Parsed::Open(Tag::Rainbow),
+        ("fade", None) => Parsed::Open(Tag::Fade),
+        _ => return None,
+    };
+    Some(parsed)
+}
+
+fn bad_value(tag: &str, problems: &mut Vec<String>) -> Option<Parsed> {
+    problems.push(format!("<{}> has a bad value", tag));
+    None
+}
+
+fn parse_pixels(value: &str) -> Option<f32> {
+    value
+        .parse::<f32>()
+        .ok()
+        .filter(|pixels| pixels.is_finite() && *pixels >= 0.0)
+}
+
+fn parse_color(value: &str) -> Option<[u8; 4]> {
+    let hex = value.strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digit = |index: usize| u8::from_str_radix(&hex[index..index + 1], 16).ok();
+    let byte = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).ok();
+    match hex.len() {
+        3 => Some([digit(0)? * 17, digit(1)? * 17, digit(2)? * 17, 255]),
+        6 => Some([byte(0)?, byte(2)?, byte(4)?, 255]),
+        8 => Some([byte(0)?, byte(2)?, byte(4)?, byte(6)?]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_styles() {
+        let markup = parse_markup("a<b>b<i>c<size=50%>d</size></i>e</b>f");
+        assert_eq!(markup.text, "abcdef");
+        assert!(markup.problems.is_empty());
+        let styles: Vec<Style> = markup.runs.iter().map(|run| run.style).collect();
+        let bold = Style { bold: true, ..Style::default() };
+        let bold_italic = Style { italic: true, ..bold };
+        assert_eq!(
+            styles,
+            [
+                Style::default(),
+                bold,
+                bold_italic,
+                Style { scale: 0.5, ..bold_italic },
+                bold,
+                Style::default(),
+            ]
+        );
+        assert_eq!(markup.style_at(3).scale, 0.5);
+    }
+
+    #[test]
+    fn tags_close_out_of_order() {
+        let markup = parse_markup("<b><i>x</b>y</i>z");
+        assert!(markup.problems.is_empty());
+        assert!(markup.style_at(1).italic && !markup.style_at(1).bold);
+        assert_eq!(markup.style_at(2), Style::default());
+    }
+
+    #[test]
+    fn stray_and_unclosed_tags() {
+        let markup = parse_markup("</b>x<i>y</unknown>");
+        assert_eq!(markup.text, "xy</unknown>");
+        assert_eq!(
+            markup.problems,
+            ["</b> closes a tag that isn't open", "<i> is never closed"]
+        );
+    }
+
+    #[test]
+    fn literal_brackets() {
+        let markup = parse_markup("a <<b> b < c <unknown> <b");
+        assert_eq!(markup.text, "a <b> b < c <unknown> <b");
+        assert!(markup.problems.is_empty());
+        assert_eq!(markup.runs.len(), 1);
+        assert_eq!(plain_text(&escape_markup("<b>x</b>")), "<b>x</b>");
+    }
+
+    #[test]
+    fn bad_values() {
+        let markup = parse_markup("<size=0>a<color=red>b<pause=-1>c<shake=x>d<icon=>e<b=1>f");
+        assert_eq!(markup.text, "<size=0>a<color=red>b<pause=-1>c<shake=x>d<icon=>e<b=1>f");
+        assert_eq!(
+            markup.problems,
+            [
+                "<size=0> has a bad value",
+                "<color=red> has a bad value",
+                "<pause=-1> has a bad value",
+                "<shake=x> has a bad value",
+                "<icon=> has a bad value",
+            ]
+        );
+        assert!(markup.pauses.is_empty() && markup.icons.is_empty());
+    }
+
+    #[test]
+    fn values() {
+        let markup = parse_markup("<color=#f80>a</color><pause>b<pause=1.5><icon=seal.png>");
+        assert_eq!(markup.style_at(0).color, Some([255, 136, 0, 255]));
+        assert_eq!(
+            markup.pauses,
+            [
+                Pause { at: 1, seconds: DEFAULT_PAUSE },
+                Pause { at: 2, seconds: 1.5 },
+            ]
+        );
+        assert_eq!(markup.icons, [Icon { at: 2, path: "seal.png".to_string() }]);
+        assert_eq!(markup.text, format!("ab{}", ICON_CHAR));
+        assert_eq!(plain_text("<b>ab</b><icon=seal.png>"), "ab");
+    }
+}
@@ -0,0 +1,260 @@
+//! Substitution of bracketed placeholders in dialogue text.
+//!
+//! `[Name]` is replaced with the value of the script variable `Name`,
+//! `[Name|stranger]` falls back to `stranger` while the variable isn't set
+//! and `[plural coins|coin|coins]` picks a word by whether `coins` is 1.
+//! `[[` and `]]` stand for literal brackets.
+//!
+//! Values of variables are escaped for `markup`, so a name the player typed
+//! in shows as typed rather than as tags.
+use std::collections::BTreeSet;
+
+use crate::{
+    expr::{parse_assignment, Value, Variables},
+    markup::escape_markup,
+    script::{ScriptItem, Scripts},
+};
+
+/// Variables that are filled in by built-in actions rather than `set`.
+pub const PROVIDED_VARIABLES: &[&str] = &["Name"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Placeholder {
+    Variable {
+        name: String,
+        default: Option<String>,
+    },
+    Plural {
+        name: String,
+        singular: String,
+        plural: String,
+    },
+}
+
+impl Placeholder {
+    pub fn variable(&self) -> &str {
+        match self {
+            Placeholder::Variable { name, .. } | Placeholder::Plural { name, .. } => name,
+        }
+    }
+}
+
+fn parse_placeholder(inner: &str) -> Result<Placeholder, String> {
+    let inner = inner.trim();
+    if let Some(rest) = inner.strip_prefix("plural ") {
+        let mut parts = rest.split('|').map(str::trim);
+        return match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(name), Some(singular), Some(plural), None) if is_variable_name(name) => {
+                Ok(Placeholder::Plural {
+                    name: name.to_string(),
+                    singular: singular.to_string(),
+                    plural: plural.to_string(),
+                })
+            }
+            _ => Err(format!(
+                "[{}] should look like [plural variable|singular|plural]",
+                inner
+            )),
+        };
+    }
+    let (name, default) = match inner.split_once('|') {
+        Some((name, default)) => (name.trim(), Some(default.trim().to_string())),
+        None => (inner, None),
+    };
+    if !is_variable_name(name) {
+        return Err(format!("[{}] isn't a variable name", inner));
+    }
+    Ok(Placeholder::Variable {
+        name: name.to_string(),
+        default,
+    })
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+enum Segment<'a> {
+    Literal(&'a str),
+    Placeholder(&'a str),
+    Unterminated(&'a str),
+}
+
+fn segments(text: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(['[', ']']) {
+        if rest[start..].starts_with("[[") || rest[start..].starts_with("]]") {
+            segments.push(Segment::Literal(&rest[..start + 1]));
+            rest = &rest[start + 2..];
+            continue;
+        }
+        if rest[start..].starts_with(']') {
+            segments.push(Segment::Literal(&rest[..start + 1]));
+            rest = &rest[start + 1..];
+            continue;
+        }
+        segments.push(Segment::Literal(&rest[..start]));
+        match rest[start..].find(']') {
+            Some(end) => {
+                segments.push(Segment::Placeholder(&rest[start + 1..start + end]));
+                rest = &rest[start + end + 1..];
+            }
+            None => {
+                segments.push(Segment::Unterminated(&rest[start..]));
+                rest = "";
+            }
+        }
+    }
+    segments.push(Segment::Literal(rest));
+    segments
+}
+
+/// All placeholders in `text`, or the problems with the malformed ones.
+pub fn placeholders(text: &str) -> Vec<Result<Placeholder, String>> {
+    segments(text)
+        .into_iter()
+        .filter_map(|segment| match segment {
+            Segment::Literal(_) => None,
+            Segment::Placeholder(inner) => Some(parse_placeholder(inner)),
+            Segment::Unterminated(rest) => Some(Err(format!("{} is missing a ]", rest))),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interpolated {
+    pub text: String,
+    /// Placeholders that couldn't be filled in and were left as written.
+    pub problems: Vec<String>,
+}
+
+pub fn interpolate(text: &str, variables: &Variables) -> Interpolated {
+    let mut result = Interpolated {
+        text: String::with_capacity(text.len()),
+        problems: Vec::new(),
+    };
+    for segment in segments(text) {
+        match segment {
+            Segment::Literal(literal) => result.text.push_str(literal),
+            Segment::Unterminated(rest) => {
+                result.problems.push(format!("{} is missing a ]", rest));
+                result.text.push_str(rest);
+            }
+            Segment::Placeholder(inner) => match fill(inner, variables) {
+                Ok(value) => result.text.push_str(&value),
+                Err(problem) => {
+                    result.problems.push(problem);
+                    result.text.push('[');
+                    result.text.push_str(inner);
+                    result.text.push(']');
+                }
+            },
+        }
+    }
+    result
+}
+
+fn fill(inner: &str, variables: &Variables) -> Result<String, String> {
+    match parse_placeholder(inner)? {
+        Placeholder::Variable { name, default } => match (variables.get(&name), default) {
+            (Some(value), _) => Ok(escape_markup(&value.to_string())),
+            (None, Some(default)) => Ok(default),
+            (None, None) => Err(format!("[{}] refers to unset variable {}", inner, name)),
+        },
+        Placeholder::Plural {
+            name,
+            singular,
+            plural,
+        } => match variables.get(&name) {
+            Some(Value::Number(count)) if *count == 1.0 => Ok(singular),
+            Some(Value::Number(_)) => Ok(plural),
+            Some(value) => Err(format!(
+                "[{}] needs a number but {} is a {}",
+                inner,
+                name,
+                value.type_name()
+            )),
+            None => Err(format!("[{}] refers to unset variable {}", inner, name)),
+        },
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaceholderIssue {
+    pub script: String,
+    pub index: usize,
+    pub message: String,
+}
+
+/// Variables that some `set` item or built-in action assigns.
+pub fn known_variables(scripts: &Scripts) -> BTreeSet<String> {
+    let mut known: BTreeSet<String> = PROVIDED_VARIABLES.iter().map(|name| name.to_string()).collect();
+    for (_, items) in scripts.iter() {
+        for item in items {
+            if let ScriptItem::Set(set) = item {
+                if let Ok(assignment) = parse_assignment(&set.set) {
+                    known.insert(assignment.name);
+                }
+            }
+        }
+    }
+    known
+}
+
+/// Reports malformed placeholders and placeholders without a default whose
+/// variable is never assigned, in blurps and choice options.
+pub fn check_placeholders(scripts: &Scripts) -> Vec<PlaceholderIssue> {
+    let known = known_variables(scripts);
+    let mut issues = Vec::new();
+    for (script, items) in scripts.iter() {
+        for (index, item) in items.iter().enumerate() {
+            let texts: Vec<&str> = match item {
+                ScriptItem::Blurp(blurp) => vec![blurp.text.as_str()],
+                ScriptItem::Choice(choice) => {
+                    choice.choice.iter().map(|option| option.text.as_str()).collect()
+                }
+                _ => continue,
+            };
+            for text in texts {
+                for placeholder in placeholders(text) {
+                    let message = match placeholder {
+                        Err(message) => message,
+                        Ok(Placeholder::Variable { default: Some(_), .. }) => continue,
+                        Ok(placeholder) if known.contains(placeholder.variable()) => continue,
+                        Ok(placeholder) => format!(
+                            "placeholder refers to {} which is never set",
+                            placeholder.variable()
+                        ),
+                    };
+                    issues.push(PlaceholderIssue {
+                        script: script.clone(),
+                        index,
+                        message,
+                    });
+                }
+            }
+        }
+    }
+    issues.sort_by(|a, b| (&a.script, a.index).cmp(&(&b.script, b.index)));
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markup::plain_text;
+
+    #[test]
+    fn values_cant_add_markup() {
+        let mut variables = Variables::new();
+        variables.set("Name", Value::Str("<b>Bob<icon=secret.png>".to_string()));
+        let interpolated = interpolate("Hi <i>[Name]</i>", &variables);
+        assert_eq!(interpolated.text, "Hi <i><<b>Bob<<icon=secret.png></i>");
+        assert_eq!(plain_text(&interpolated.text), "Hi <b>Bob<icon=secret.png>");
+    }
+}
@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+
+/// Topic the runner keeps in sync with the active quests, so an
+/// `offer_topics` hub for it lets the player resume any of them.
+pub const OPEN_QUESTS_TOPIC: &str = "open_quests";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuestStatus {
+    Active,
+    Completed,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Quest {
+    /// Name shown in the quest log and in topic hubs.
+    pub name: String,
+    /// Script that starts the quest, also used as its key.
+    pub script: String,
+    pub status: QuestStatus,
+}
+
+/// Quest journal, in the order the quests were added.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct QuestLog {
+    quests: Vec<Quest>,
+}
+impl QuestLog {
+    pub fn new() -> Self {
+        Self { quests: Vec::new() }
+    }
+    pub fn get(&self, script: &str) -> Option<&Quest> {
+        self.quests.iter().find(|quest| quest.script == script)
+    }
+    /// Returns false if the quest is already in the log, whatever its status.
+    pub fn add_quest(&mut self, script: String, name: String) -> bool {
+        if self.get(&script).is_some() {
+            return false;
+        }
+        self.quests.push(Quest {
+            name,
+            script,
+            status: QuestStatus::Active,
+        });
+        true
+    }
+    pub fn complete_quest(&mut self, script: &str) -> bool {
+        self.set_status(script, QuestStatus::Completed)
+    }
+    pub fn fail_quest(&mut self, script: &str) -> bool {
+        self.set_status(script, QuestStatus::Failed)
+    }
+    /// Only active quests can be completed or failed.
+    fn set_status(&mut self, script: &str, status: QuestStatus) -> bool {
+        match self.quests.iter_mut().find(|quest| quest.script == script) {
+            Some(quest) if quest.status == QuestStatus::Active => {
+                quest.status = status;
+                true
+            }
+            _ => false,
+        }
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &Quest> {
+        self.quests.iter()
+    }
+    pub fn active(&self) -> impl Iterator<Item = &Quest> {
+        self.quests
+            .iter()
+            .filter(|quest| quest.status == QuestStatus::Active)
+    }
+    /// Completed and failed quests.
+    pub fn finished(&self) -> impl Iterator<Item = &Quest> {
+        self.quests
+            .iter()
+            .filter(|quest| quest.status != QuestStatus::Active)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_quest() {
+        let mut log = QuestLog::new();
+        assert!(log.add_quest("Bakery".to_string(), "Find the baker".to_string()));
+        assert!(log.add_quest("Well".to_string(), "Fix the well".to_string()));
+        assert!(!log.add_quest("Bakery".to_string(), "Another name".to_string()));
+        assert_eq!(
+            log.get("Bakery"),
+            Some(&Quest {
+                name: "Find the baker".to_string(),
+                script: "Bakery".to_string(),
+                status: QuestStatus::Active,
+            })
+        );
+        let scripts: Vec<_> = log.iter().map(|quest| quest.script.as_str()).collect();
+        assert_eq!(scripts, ["Bakery", "Well"]);
+    }
+
+    #[test]
+    fn finish_quests() {
+        let mut log = QuestLog::new();
+        log.add_quest("Bakery".to_string(), "Find the baker".to_string());
+        log.add_quest("Well".to_string(), "Fix the well".to_string());
+        log.add_quest("Mill".to_string(), "Oil the mill".to_string());
+        assert!(log.complete_quest("Bakery"));
+        assert!(log.fail_quest("Well"));
+        assert_eq!(log.get("Bakery").unwrap().status, QuestStatus::Completed);
+        assert_eq!(log.get("Well").unwrap().status, QuestStatus::Failed);
+
+        // Finished quests stay finished.
+        assert!(!log.complete_quest("Bakery"));
+        assert!(!log.fail_quest("Bakery"));
+        assert!(!log.complete_quest("Well"));
+        assert_eq!(log.get("Bakery").unwrap().status, QuestStatus::Completed);
+        assert!(!log.complete_quest("Missing"));
+        // Adding a finished quest again doesn't restart it.
+        assert!(!log.add_quest("Well".to_string(), "Fix the well".to_string()));
+        assert_eq!(log.get("Well").unwrap().status, QuestStatus::Failed);
+
+        let active: Vec<_> = log.active().map(|quest| quest.script.as_str()).collect();
+        let finished: Vec<_> = log.finished().map(|quest| quest.script.as_str()).collect();
+        assert_eq!(active, ["Mill"]);
+        assert_eq!(finished, ["Bakery", "Well"]);
+    }
+}
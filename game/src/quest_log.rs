@@ -0,0 +1,76 @@
+use fyrox::{
+    asset::manager::ResourceManager,
+    core::{algebra::Vector2, pool::Handle},
+    gui::{
+        formatted_text::WrapMode,
+        message::MessageDirection,
+        stack_panel::StackPanelBuilder,
+        text::TextBuilder,
+        widget::{WidgetBuilder, WidgetMessage},
+        BuildContext, UiNode, UserInterface,
+    },
+};
+
+use crate::{
+    nine_patch::{center_widget_builder, NinePatchBuilder},
+    quest::{QuestLog, QuestStatus},
+};
+
+/// Builds the quest log panel: active quests first, then the completed and
+/// failed ones.
+pub fn build_quest_log(
+    ctx: &mut BuildContext,
+    resource_manager: &ResourceManager,
+    quests: &QuestLog,
+) -> Handle<UiNode> {
+    let mut wb = center_widget_builder()
+        .with_child(quest_log_line(ctx, "Active quests"));
+    let mut any_active = false;
+    for quest in quests.active() {
+        any_active = true;
+        wb = wb.with_child(quest_log_line(ctx, &format!("  {}", quest.name)));
+    }
+    if !any_active {
+        wb = wb.with_child(quest_log_line(ctx, "  none"));
+    }
+
+    wb = wb.with_child(quest_log_line(ctx, "Completed quests"));
+    let mut any_finished = false;
+    for quest in quests.finished() {
+        any_finished = true;
+        let line = if quest.status == QuestStatus::Failed {
+            format!("  {} (failed)", quest.name)
+        } else {
+            format!("  {}", quest.name)
+        };
+        wb = wb.with_child(quest_log_line(ctx, &line));
+    }
+    if !any_finished {
+        wb = wb.with_child(quest_log_line(ctx, "  none"));
+    }
+
+    let list = StackPanelBuilder::new(wb).build(ctx);
+    NinePatchBuilder::new(
+        resource_manager,
+        "data/9boxblur.png",
+        40,
+        41,
+        40,
+        41,
+        81,
+        81,
+    )
+    .with_center(list)
+    .build(ctx)
+}
+
+pub fn remove_quest_log(ui: &UserInterface, panel: Handle<UiNode>) {
+    ui.send_message(WidgetMessage::remove(panel, MessageDirection::ToWidget));
+}
+
+fn quest_log_line(ctx: &mut BuildContext, text: &str) -> Handle<UiNode> {
+    TextBuilder::new(WidgetBuilder::new().with_max_size(Vector2::new(300.0, f32::INFINITY)))
+        .with_wrap(WrapMode::Word)
+        .with_text(text)
+        .build(ctx)
+}
@@ -0,0 +1,1063 @@
+//! Dialogue runtime.
+//!
+//! `DialogueRunner` walks `Scripts` one item at a time and stops whenever the
+//! player has to do something. It doesn't know about the `UserInterface`, the
+//! plugin reads `state()` after every input and updates the widgets to match.
+use std::collections::VecDeque;
+
+use fyrox::core::log::Log;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    action::{ActionOutcome, ActionRegistry, ActionRequest},
+    expr::{eval_condition, parse_assignment, Value, Variables},
+    history::{History, HistoryEntry},
+    localization::{blurp_id, option_id, StringTable},
+    placeholder::interpolate,
+    quest::{QuestLog, OPEN_QUESTS_TOPIC},
+    script::{ScriptItem, Scripts},
+    seen::SeenLines,
+    topic_list::{ItemCondition, TopicList},
+    ScriptPos,
+};
+
+/// Upper bound on the number of non-blocking items processed for one input,
+/// so a `jump` loop without any blurps can't hang the game.
+const MAX_STEPS: usize = 10_000;
+
+/// How many blurps and choices `DialogueRunner::rollback` can go back by
+/// default.
+pub const ROLLBACK_LIMIT: usize = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub character: String,
+    pub text: String,
+    /// Whether the line was read before it was shown this time, in this or
+    /// an earlier playthrough.
+    pub seen: bool,
+}
+
+/// A choice option whose condition allowed it to be shown.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShownChoice {
+    pub text: String,
+    pub jump: String,
+    /// False when the option is greyed out.
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunnerState {
+    /// A blurp is on screen and the runner waits for `DialogueInput::Continue`.
+    ShowingLine(Line),
+    /// A `choice` menu is open and the runner waits for `DialogueInput::Choose`.
+    WaitingForChoice(Vec<ShownChoice>),
+    /// An `offer_topics` hub is open and the runner waits for
+    /// `DialogueInput::PickTopic` or `DialogueInput::LeaveTopics`.
+    WaitingForTopic { topic: String, exit: bool },
+    /// An action handler asked for something from the player, e.g. their
+    /// name, and the runner waits for `DialogueInput::SubmitText`.
+    WaitingForAction(ActionRequest),
+    /// A blurp with a `wait` is on screen. `update` continues once `remaining`
+    /// seconds have passed, the game only calls it in auto mode. Otherwise
+    /// it's the same as `ShowingLine`.
+    WaitingForTimer { line: Line, remaining: f64 },
+    /// Nothing is running.
+    Finished,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DialogueInput {
+    /// Advance past the line on screen.
+    Continue,
+    /// Pick the option with this index from the open choice menu.
+    Choose(usize),
+    /// Run the script of this item from the open topic hub.
+    PickTopic(String),
+    /// Close the open topic hub and carry on after it.
+    LeaveTopics,
+    /// Answer an `ActionRequest::TextInput`, it must not be blank.
+    SubmitText(String),
+}
+
+/// Side effects the runner can't perform itself. They're queued while
+/// stepping and collected by the caller with `DialogueRunner::drain_events`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DialogueEvent {
+    AddQuest { script: String, name: String },
+    Animation(String),
+    CompleteQuest(String),
+    Cue(String),
+    FailQuest(String),
+}
+
+/// An entry of the open topic hub.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopicItem {
+    /// Script that runs when the entry is picked.
+    pub item: String,
+    pub label: String,
+    /// False when the entry is greyed out.
+    pub enabled: bool,
+}
+
+/// What `DialogueRunner::restore` puts back, e.g. from a save game.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedRunner {
+    /// Has to satisfy `DialogueRunner::can_stop_at`.
+    pub position: Option<ScriptPos>,
+    pub call_stack: Vec<ScriptPos>,
+    pub topics: TopicList,
+    pub quests: QuestLog,
+    #[serde(default)]
+    pub variables: Variables,
+    /// Shown in the backlog after loading.
+    #[serde(default)]
+    pub history: History,
+}
+
+/// Runner state on arriving at a blurp or choice, before its line went into
+/// the history.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    pos: ScriptPos,
+    call_stack: Vec<ScriptPos>,
+    topics: TopicList,
+    quests: QuestLog,
+    variables: Variables,
+    history: History,
+}
+
+#[derive(Debug)]
+pub struct DialogueRunner {
+    /// Item the runner is currently stopped on.
+    pos: Option<ScriptPos>,
+    /// Where to resume once the current script ends.
+    call_stack: Vec<ScriptPos>,
+    state: RunnerState,
+    events: Vec<DialogueEvent>,
+    topics: TopicList,
+    quests: QuestLog,
+    variables: Variables,
+    actions: ActionRegistry,
+    /// Translations of the text shown, empty for the source language.
+    strings: StringTable,
+    history: History,
+    /// Stop that was recorded last, so entering it again after a reload or
+    /// load doesn't add its line to `history` twice. Cleared by every input
+    /// that moves on.
+    entered: Option<ScriptPos>,
+    /// One per blurp and choice reached, oldest first. The last one is the
+    /// stop the runner is on.
+    snapshots: VecDeque<Snapshot>,
+    rollback_limit: usize,
+    /// Every blurp ever shown, shared by all playthroughs.
+    seen: SeenLines,
+}
+
+impl Default for DialogueRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DialogueRunner {
+    pub fn new() -> Self {
+        Self {
+            pos: None,
+            call_stack: Vec::new(),
+            state: RunnerState::Finished,
+            events: Vec::new(),
+            topics: TopicList::new(),
+            quests: QuestLog::new(),
+            variables: Variables::new(),
+            actions: ActionRegistry::with_builtin_actions(),
+            strings: StringTable::default(),
+            history: History::new(),
+            entered: None,
+            snapshots: VecDeque::new(),
+            rollback_limit: ROLLBACK_LIMIT,
+            seen: SeenLines::new(),
+        }
+    }
+
+    pub fn state(&self) -> &RunnerState {
+        &self.state
+    }
+
+    pub fn position(&self) -> Option<&ScriptPos> {
+        self.pos.as_ref()
+    }
+
+    pub fn call_stack(&self) -> &[ScriptPos] {
+        &self.call_stack
+    }
+
+    pub fn topics(&self) -> &TopicList {
+        &self.topics
+    }
+
+    pub fn topics_mut(&mut self) -> &mut TopicList {
+        &mut self.topics
+    }
+
+    pub fn quests(&self) -> &QuestLog {
+        &self.quests
+    }
+
+    pub fn quests_mut(&mut self) -> &mut QuestLog {
+        &mut self.quests
+    }
+
+    /// Switches the language of the text, the line or menu on screen keeps
+    /// its old text until `reload`.
+    pub fn set_strings(&mut self, strings: StringTable) {
+        self.strings = strings;
+    }
+
+    /// Lines shown and choices made so far, oldest first.
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn history_mut(&mut self) -> &mut History {
+        &mut self.history
+    }
+
+    pub fn seen(&self) -> &SeenLines {
+        &self.seen
+    }
+
+    /// Replaces the seen lines, e.g. with the ones read at startup.
+    pub fn set_seen(&mut self, seen: SeenLines) {
+        self.seen = seen;
+    }
+
+    pub fn variables(&self) -> &Variables {
+        &self.variables
+    }
+
+    pub fn variables_mut(&mut self) -> &mut Variables {
+        &mut self.variables
+    }
+
+    pub fn actions_mut(&mut self) -> &mut ActionRegistry {
+        &mut self.actions
+    }
+
+    /// Entries the open topic hub should list, in display order. Items that
+    /// start a quest are labelled with the quest's name, items whose
+    /// condition is false are hidden or greyed out.
+    pub fn topic_items(&self) -> Vec<TopicItem> {
+        let RunnerState::WaitingForTopic { topic, .. } = &self.state else {
+            return Vec::new();
+        };
+        let Some(items) = self.topics.get(topic) else {
+            return Vec::new();
+        };
+        items
+            .iter()
+            .filter_map(|item| {
+                let enabled = self.topic_item_enabled(topic, item);
+                let shown = enabled
+                    || self
+                        .topics
+                        .condition(topic, item)
+                        .is_some_and(|condition| condition.show_disabled);
+                shown.then(|| TopicItem {
+                    item: item.clone(),
+                    label: self.topic_label(item),
+                    enabled,
+                })
+            })
+            .collect()
+    }
+
+    fn topic_label(&self, item: &str) -> String {
+        self.quests
+            .get(item)
+            .map_or_else(|| item.to_string(), |quest| quest.name.clone())
+    }
+
+    fn topic_item_enabled(&self, topic: &str, item: &str) -> bool {
+        match self.topics.condition(topic, item) {
+            Some(condition) => self.check_condition(
+                &condition.condition,
+                &format!("item {} of topic {}", item, topic),
+            ),
+            None => true,
+        }
+    }
+
+    /// Fills in the placeholders of `text`, logging the ones that can't be
+    /// filled in against `context`.
+    fn interpolate(&self, text: &str, context: &str) -> String {
+        let interpolated = interpolate(text, &self.variables);
+        for problem in interpolated.problems {
+            Log::warn(format!("{} in {}", problem, context));
+        }
+        interpolated.text
+    }
+
+    /// Evaluates `condition`, logging errors against `context` and treating
+    /// them as false.
+    fn check_condition(&self, condition: &str, context: &str) -> bool {
+        match eval_condition(condition, &self.variables) {
+            Ok(value) => value,
+            Err(err) => {
+                Log::err(format!(
+                    "bad condition {:?} in {}: {}",
+                    condition, context, err
+                ));
+                false
+            }
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.state == RunnerState::Finished
+    }
+
+    /// Takes the side effects queued since the last call.
+    pub fn drain_events(&mut self) -> Vec<DialogueEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Drops whatever was running and starts `script` from the top.
+    pub fn start(&mut self, scripts: &Scripts, script: &str) {
+        self.call_stack.clear();
+        self.entered = None;
+        self.snapshots.clear();
+        if !scripts.has_script(script) {
+            Log::err(format!("tried to start non existant script {}.", script));
+            self.finish();
+            return;
+        }
+        self.run_from(
+            scripts,
+            ScriptPos {
+                script: script.to_string(),
+                index: 0,
+            },
+        );
+    }
+
+    pub fn handle_input(&mut self, scripts: &Scripts, input: DialogueInput) {
+        let Some(pos) = self.pos.clone() else {
+            Log::warn(format!("ignoring {:?}, no script is running.", input));
+            return;
+        };
+        match (self.state.clone(), input) {
+            (RunnerState::ShowingLine(_), DialogueInput::Continue)
+            | (RunnerState::WaitingForTimer { .. }, DialogueInput::Continue) => {
+                self.entered = None;
+                self.run_from(scripts, pos.next());
+            }
+            (RunnerState::WaitingForChoice(options), DialogueInput::Choose(index)) => {
+                let Some(option) = options.get(index) else {
+                    Log::warn(format!(
+                        "choice {} is out of range in {}.",
+                        index, scripts.describe(&pos.script, pos.index)
+                    ));
+                    return;
+                };
+                if !option.enabled {
+                    Log::warn(format!(
+                        "choice {} in {} is disabled.",
+                        index, scripts.describe(&pos.script, pos.index)
+                    ));
+                    return;
+                }
+                self.record_choice(option.text.clone());
+                self.jump(scripts, &pos, &option.jump);
+            }
+            (RunnerState::WaitingForTopic { topic, .. }, DialogueInput::PickTopic(item)) => {
+                if !self.topics.contains(&topic, &item) {
+                    Log::warn(format!("{} isn't an item of topic {}.", item, topic));
+                    return;
+                }
+                if !self.topic_item_enabled(&topic, &item) {
+                    Log::warn(format!("item {} of topic {} is disabled.", item, topic));
+                    return;
+                }
+                self.record_choice(self.topic_label(&item));
+                // The hub is re-offered once the item's script ends.
+                self.call_stack.push(pos.clone());
+                self.jump(scripts, &pos, &item);
+            }
+            (RunnerState::WaitingForTopic { exit: true, .. }, DialogueInput::LeaveTopics) => {
+                self.entered = None;
+                self.run_from(scripts, pos.next());
+            }
+            (
+                RunnerState::WaitingForAction(ActionRequest::TextInput { variable, .. }),
+                DialogueInput::SubmitText(text),
+            ) => {
+                let text = text.trim();
+                if text.is_empty() {
+                    Log::warn(format!(
+                        "ignoring blank text for {} in {}.",
+                        variable, scripts.describe(&pos.script, pos.index)
+                    ));
+                    return;
+                }
+                self.variables.set(&variable, Value::Str(text.to_string()));
+                self.entered = None;
+                self.run_from(scripts, pos.next());
+            }
+            (state, input) => {
+                Log::warn(format!("ignoring {:?} while in {:?}.", input, state));
+            }
+        }
+    }
+
+    /// Whether `pos` is an item the runner stops at, i.e. a valid position to
+    /// save or resume from.
+    pub fn can_stop_at(scripts: &Scripts, pos: &ScriptPos) -> bool {
+        matches!(
+            scripts.get_item(&pos.script, pos.index),
+            Some(
+                ScriptItem::Action(_)
+                    | ScriptItem::Blurp(_)
+                    | ScriptItem::Choice(_)
+                    | ScriptItem::OfferTopics(_)
+            )
+        )
+    }
+
+    /// Everything `restore` needs to carry on from here.
+    pub fn save(&self) -> SavedRunner {
+        SavedRunner {
+            position: self.pos.clone(),
+            call_stack: self.call_stack.clone(),
+            topics: self.topics.clone(),
+            quests: self.quests.clone(),
+            variables: self.variables.clone(),
+            history: self.history.clone(),
+        }
+    }
+
+    /// Replaces the whole runner state, e.g. from a save game. The item at
+    /// `saved.position` is entered again.
+    pub fn restore(&mut self, scripts: &Scripts, saved: SavedRunner) {
+        let SavedRunner {
+            position,
+            call_stack,
+            topics,
+            quests,
+            variables,
+            history,
+        } = saved;
+        self.events.clear();
+        self.topics = topics;
+        self.quests = quests;
+        self.variables = variables;
+        // The line the save stopped at is already the last entry, unless the
+        // save predates the history.
+        self.entered = if history.is_empty() { None } else { position.clone() };
+        self.history.replace(history);
+        self.snapshots.clear();
+        // Entering the line again won't snapshot it, so it's done here, from
+        // before the line went into the history like any other snapshot.
+        if let Some(pos) = &self.entered {
+            if self.rollback_limit > 0 && matches!(scripts.get_item(&pos.script, pos.index), Some(ScriptItem::Blurp(_))) {
+                let mut history = self.history.clone();
+                history.pop();
+                self.snapshots.push_back(Snapshot {
+                    pos: pos.clone(),
+                    call_stack: call_stack.clone(),
+                    topics: self.topics.clone(),
+                    quests: self.quests.clone(),
+                    variables: self.variables.clone(),
+                    history,
+                });
+            }
+        }
+        match position {
+            Some(pos) => {
+                self.call_stack = call_stack;
+                self.run_from(scripts, pos);
+            }
+            None => self.finish(),
+        }
+    }
+
+    /// How many blurps and choices `rollback` can go back by right now.
+    pub fn rollback_depth(&self) -> usize {
+        self.snapshots.len().saturating_sub(1)
+    }
+
+    /// Keeps at most `limit` steps to roll back by, 0 turns rollback off.
+    pub fn set_rollback_limit(&mut self, limit: usize) {
+        self.rollback_limit = limit;
+        let keep = if limit == 0 { 0 } else { limit + 1 };
+        while self.snapshots.len() > keep {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// Goes back `steps` blurps or choices, undoing everything that happened
+    /// since: variables, topics, quests, the call stack and the history. A
+    /// choice is offered again so it can be made differently. Effects of
+    /// action handlers outside the runner aren't undone, and no events are
+    /// queued for what was undone, the caller should refresh whatever shows
+    /// quests or topics. Returns false, changing nothing, if there aren't
+    /// that many steps to go back or the scripts changed under the stop.
+    pub fn rollback(&mut self, scripts: &Scripts, steps: usize) -> bool {
+        if steps == 0 || steps > self.rollback_depth() {
+            return false;
+        }
+        let target = self.snapshots.len() - 1 - steps;
+        if !Self::can_stop_at(scripts, &self.snapshots[target].pos) {
+            Log::warn(format!(
+                "can't roll back to {}, the scripts changed.",
+                scripts.describe(&self.snapshots[target].pos.script, self.snapshots[target].pos.index)
+            ));
+            return false;
+        }
+        // Entering the stop again takes its snapshot again.
+        self.snapshots.truncate(target + 1);
+        let Some(snapshot) = self.snapshots.pop_back() else {
+            return false;
+        };
+        self.events.clear();
+        self.call_stack = snapshot.call_stack;
+        self.topics = snapshot.topics;
+        self.quests = snapshot.quests;
+        self.variables = snapshot.variables;
+        self.history.replace(snapshot.history);
+        self.entered = None;
+        self.run_from(scripts, snapshot.pos);
+        true
+    }
+
+    /// Carries on from the same position after `scripts` were edited. If the
+    /// current script is gone the dialogue stops, call stack entries into
+    /// removed scripts are dropped.
+    pub fn reload(&mut self, scripts: &Scripts) {
+        let Some(pos) = self.pos.clone() else {
+            return;
+        };
+        self.call_stack.retain(|pos| scripts.has_script(&pos.script));
+        if !scripts.has_script(&pos.script) {
+            Log::warn(format!("script {} was removed while running", pos.script));
+            self.finish();
+            return;
+        }
+        self.run_from(scripts, pos);
+    }
+
+    /// Counts down `RunnerState::WaitingForTimer`, `dt` is in seconds.
+    pub fn update(&mut self, scripts: &Scripts, dt: f64) {
+        let RunnerState::WaitingForTimer { remaining, .. } = &mut self.state else {
+            return;
+        };
+        *remaining -= dt;
+        if *remaining <= 0.0 {
+            self.handle_input(scripts, DialogueInput::Continue);
+        }
+    }
+
+    /// Takes a snapshot for `rollback` and adds `line` to the history, unless
+    /// that was done when `pos` was entered before.
+    fn enter_stop(&mut self, pos: &ScriptPos, line: Option<&Line>) {
+        let entered = self.entered.as_ref() == Some(pos);
+        let snapshotted = self.snapshots.back().is_some_and(|snapshot| &snapshot.pos == pos);
+        if !(entered && snapshotted) && self.rollback_limit > 0 {
+            self.snapshots.push_back(Snapshot {
+                pos: pos.clone(),
+                call_stack: self.call_stack.clone(),
+                topics: self.topics.clone(),
+                quests: self.quests.clone(),
+                variables: self.variables.clone(),
+                history: self.history.clone(),
+            });
+            // One more than the limit, the last snapshot is where we are.
+            while self.snapshots.len() > self.rollback_limit + 1 {
+                self.snapshots.pop_front();
+            }
+        }
+        if !entered {
+            if let Some(line) = line {
+                self.seen.insert(pos);
+                self.history.push(HistoryEntry::Line {
+                    character: line.character.clone(),
+                    text: line.text.clone(),
+                });
+            }
+            self.entered = Some(pos.clone());
+        }
+    }
+
+    fn record_choice(&mut self, text: String) {
+        self.history.push(HistoryEntry::Choice(text));
+        self.entered = None;
+    }
+
+    fn finish(&mut self) {
+        self.pos = None;
+        self.call_stack.clear();
+        self.state = RunnerState::Finished;
+    }
+
+    fn jump(&mut self, scripts: &Scripts, from: &ScriptPos, target: &str) {
+        if !scripts.has_script(target) {
+            Log::err(format!(
+                "tried to jump to non existant script {} from script {}.",
+                target, scripts.describe(&from.script, from.index)
+            ));
+            self.finish();
+            return;
+        }
+        Log::info(format!(
+            "jumping to script {} from script {}.",
+            target, scripts.describe(&from.script, from.index)
+        ));
+        self.run_from(
+            scripts,
+            ScriptPos {
+                script: target.to_string(),
+                index: 0,
+            },
+        );
+    }
+
+    /// Steps through items starting at `pos` until one of them needs the player.
+    fn run_from(&mut self, scripts: &Scripts, mut pos: ScriptPos) {
+        for _ in 0..MAX_STEPS {
+            let Some(item) = scripts.get_item(&pos.script, pos.index) else {
+                Log::info(format!("Ran off the end of {} at {}", pos.script, pos.index));
+                match self.call_stack.pop() {
+                    Some(ret) => {
+                        pos = ret;
+                        continue;
+                    }
+                    None => {
+                        self.finish();
+                        return;
+                    }
+                }
+            };
+            match item {
+                ScriptItem::Action(action) => {
+                    let Some(handler) = self.actions.get(&action.action) else {
+                        Log::err(format!(
+                            "unknown action {:?} in {}.",
+                            action.action, scripts.describe(&pos.script, pos.index)
+                        ));
+                        pos = pos.next();
+                        continue;
+                    };
+                    if let ActionOutcome::Wait(request) = handler(&mut self.variables) {
+                        self.state = RunnerState::WaitingForAction(request);
+                        self.pos = Some(pos);
+                        return;
+                    }
+                }
+                ScriptItem::AddQuest(quest) => {
+                    if self
+                        .quests
+                        .add_quest(quest.add_quest_script.clone(), quest.add_quest_name.clone())
+                    {
+                        self.topics.insert_item_in_topic(
+                            OPEN_QUESTS_TOPIC,
+                            quest.add_quest_script.clone(),
+                        );
+                        self.events.push(DialogueEvent::AddQuest {
+                            script: quest.add_quest_script.clone(),
+                            name: quest.add_quest_name.clone(),
+                        });
+                    }
+                }
+                ScriptItem::AddTopicItem(add) => {
+                    let condition = add.condition.clone().map(|condition| ItemCondition {
+                        condition,
+                        show_disabled: add.show_disabled,
+                    });
+                    self.topics.insert_item_with_condition(
+                        &add.add_topic_item,
+                        add.item.clone(),
+                        condition,
+                    );
+                }
+                ScriptItem::Animation(animation) => {
+                    self.events
+                        .push(DialogueEvent::Animation(animation.animation.clone()));
+                }
+                ScriptItem::Blurp(blurp) => {
+                    let line = Line {
+                        character: blurp.character.clone(),
+                        text: self.interpolate(
+                            self.strings.translate(&blurp_id(&pos.script, pos.index, blurp), &blurp.text),
+                            &scripts.describe(&pos.script, pos.index),
+                        ),
+                        seen: self.seen.contains(&pos),
+                    };
+                    self.enter_stop(&pos, Some(&line));
+                    self.state = match blurp.wait {
+                        Some(wait) => RunnerState::WaitingForTimer {
+                            line,
+                            remaining: wait,
+                        },
+                        None => RunnerState::ShowingLine(line),
+                    };
+                    self.pos = Some(pos);
+                    return;
+                }
+                ScriptItem::Choice(choice) => {
+                    let mut shown = Vec::new();
+                    for (index, option) in choice.choice.iter().enumerate() {
+                        let enabled = match &option.condition {
+                            Some(condition) => self.check_condition(
+                                condition,
+                                &format!("{} option {}", scripts.describe(&pos.script, pos.index), index),
+                            ),
+                            None => true,
+                        };
+                        if enabled || option.show_disabled {
+                            shown.push(ShownChoice {
+                                text: self.interpolate(
+                                    self.strings.translate(
+                                        &option_id(&pos.script, pos.index, index, option),
+                                        &option.text,
+                                    ),
+                                    &format!("{} option {}", scripts.describe(&pos.script, pos.index), index),
+                                ),
+                                jump: option.jump.clone(),
+                                enabled,
+                            });
+                        }
+                    }
+                    if !shown.iter().any(|option| option.enabled) {
+                        Log::warn(format!(
+                            "every option of the choice in {} is disabled.",
+                            scripts.describe(&pos.script, pos.index)
+                        ));
+                    }
+                    self.enter_stop(&pos, None);
+                    self.state = RunnerState::WaitingForChoice(shown);
+                    self.pos = Some(pos);
+                    return;
+                }
+                ScriptItem::CompleteQuest(quest) => {
+                    if self.quests.complete_quest(&quest.complete_quest) {
+                        self.topics
+                            .remove_item(OPEN_QUESTS_TOPIC, &quest.complete_quest);
+                        self.events
+                            .push(DialogueEvent::CompleteQuest(quest.complete_quest.clone()));
+                    } else {
+                        Log::warn(format!(
+                            "can't complete quest {} in {}, it isn't active.",
+                            quest.complete_quest, scripts.describe(&pos.script, pos.index)
+                        ));
+                    }
+                }
+                ScriptItem::Cue(cue) => {
+                    self.events.push(DialogueEvent::Cue(cue.cue.clone()));
+                }
+                ScriptItem::End(_) => match self.call_stack.pop() {
+                    Some(ret) => {
+                        pos = ret;
+                        continue;
+                    }
+                    None => {
+                        self.finish();
+                        return;
+                    }
+                },
+                ScriptItem::FailQuest(quest) => {
+                    if self.quests.fail_quest(&quest.fail_quest) {
+                        self.topics.remove_item(OPEN_QUESTS_TOPIC, &quest.fail_quest);
+                        self.events
+                            .push(DialogueEvent::FailQuest(quest.fail_quest.clone()));
+                    } else {
+                        Log::warn(format!(
+                            "can't fail quest {} in {}, it isn't active.",
+                            quest.fail_quest, scripts.describe(&pos.script, pos.index)
+                        ));
+                    }
+                }
+                ScriptItem::If(branch) => {
+                    let target = if self
+                        .check_condition(&branch.condition, &scripts.describe(&pos.script, pos.index))
+                    {
+                        Some(&branch.jump)
+                    } else {
+                        branch.else_jump.as_ref()
+                    };
+                    if let Some(target) = target {
+                        if !scripts.has_script(target) {
+                            Log::err(format!(
+                                "tried to jump to non existant script {} from script {}.",
+                                target, scripts.describe(&pos.script, pos.index)
+                            ));
+                            self.finish();
+                            return;
+                        }
+                        pos = ScriptPos {
+                            script: target.clone(),
+                            index: 0,
+                        };
+                        continue;
+                    }
+                }
+                ScriptItem::Jump(jump) => {
+                    if !scripts.has_script(&jump.jump) {
+                        Log::err(format!(
+                            "tried to jump to non existant script {} from script {}.",
+                            jump.jump, scripts.describe(&pos.script, pos.index)
+                        ));
+                        self.finish();
+                        return;
+                    }
+                    pos = ScriptPos {
+                        script: jump.jump.clone(),
+                        index: 0,
+                    };
+                    continue;
+                }
+                ScriptItem::OfferTopics(offer) => {
+                    if !offer.exit
+                        && self
+                            .topics
+                            .get(&offer.offer_topics)
+                            .is_none_or(|items| items.is_empty())
+                    {
+                        Log::warn(format!(
+                            "topic {} offered in {} has no items and no exit.",
+                            offer.offer_topics, scripts.describe(&pos.script, pos.index)
+                        ));
+                    }
+                    self.state = RunnerState::WaitingForTopic {
+                        topic: offer.offer_topics.clone(),
+                        exit: offer.exit,
+                    };
+                    self.pos = Some(pos);
+                    return;
+                }
+                ScriptItem::RemoveTopicItem(remove) => {
+                    self.topics.remove_item(&remove.remove_topic_item, &remove.item);
+                }
+                ScriptItem::Set(set) => {
+                    let applied = parse_assignment(&set.set)
+                        .and_then(|assignment| assignment.apply(&mut self.variables));
+                    if let Err(err) = applied {
+                        Log::err(format!(
+                            "bad set {:?} in {}: {}",
+                            set.set, scripts.describe(&pos.script, pos.index), err
+                        ));
+                    }
+                }
+            }
+            pos = pos.next();
+        }
+        Log::err(format!(
+            "gave up after {} steps without reaching a line, stopped in {}.",
+            MAX_STEPS, scripts.describe(&pos.script, pos.index)
+        ));
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{save::SaveGame, screenplay::parse_screenplay};
+
+    const STORY: &str = r#"
+== Start ==
+Narrator: Hello.
+@set visits = 1
+* Go -> Go
+* Locked -> Locked if? visits > 5
+* Hidden -> Locked if visits > 5
+
+== Go ==
+Narrator [1.5]: Waiting.
+@add_quest Quest The quest
+@add_topic_item hub Talk
+@offer_topics hub exit
+Narrator: Bye.
+
+== Talk ==
+Pharaoh: Talked.
+
+== Locked ==
+Narrator: Locked.
+
+== Quest ==
+Narrator: Quest.
+"#;
+
+    fn scripts() -> Scripts {
+        parse_screenplay(STORY, Path::new("story.screenplay")).unwrap()
+    }
+
+    /// Text of the line on screen, if there is one.
+    fn line(runner: &DialogueRunner) -> Option<&str> {
+        match runner.state() {
+            RunnerState::ShowingLine(line) | RunnerState::WaitingForTimer { line, .. } => Some(&line.text),
+            _ => None,
+        }
+    }
+
+    /// Runs `Start` up to the hub, past the choice and the timed line.
+    fn to_hub(runner: &mut DialogueRunner, scripts: &Scripts) {
+        runner.start(scripts, "Start");
+        runner.handle_input(scripts, DialogueInput::Continue);
+        runner.handle_input(scripts, DialogueInput::Choose(0));
+        runner.handle_input(scripts, DialogueInput::Continue);
+    }
+
+    #[test]
+    fn continue_and_choose() {
+        let scripts = scripts();
+        let mut runner = DialogueRunner::new();
+        runner.start(&scripts, "Start");
+        assert_eq!(line(&runner), Some("Hello."));
+        runner.handle_input(&scripts, DialogueInput::Continue);
+        let choice = RunnerState::WaitingForChoice(vec![
+            ShownChoice {
+                text: "Go".to_string(),
+                jump: "Go".to_string(),
+                enabled: true,
+            },
+            ShownChoice {
+                text: "Locked".to_string(),
+                jump: "Locked".to_string(),
+                enabled: false,
+            },
+        ]);
+        assert_eq!(runner.state(), &choice);
+
+        // Disabled and out of range options, and the wrong input, change nothing.
+        runner.handle_input(&scripts, DialogueInput::Choose(1));
+        runner.handle_input(&scripts, DialogueInput::Choose(2));
+        runner.handle_input(&scripts, DialogueInput::Continue);
+        assert_eq!(runner.state(), &choice);
+
+        runner.handle_input(&scripts, DialogueInput::Choose(0));
+        assert_eq!(line(&runner), Some("Waiting."));
+        assert_eq!(runner.variables().get("visits"), Some(&Value::Number(1.0)));
+    }
+
+    #[test]
+    fn wait_timer() {
+        let scripts = scripts();
+        let mut runner = DialogueRunner::new();
+        runner.start(&scripts, "Start");
+        runner.handle_input(&scripts, DialogueInput::Continue);
+        runner.handle_input(&scripts, DialogueInput::Choose(0));
+        assert!(matches!(runner.state(), RunnerState::WaitingForTimer { remaining, .. } if *remaining == 1.5));
+        runner.update(&scripts, 1.0);
+        assert_eq!(line(&runner), Some("Waiting."));
+        runner.update(&scripts, 0.6);
+        assert_eq!(
+            runner.state(),
+            &RunnerState::WaitingForTopic {
+                topic: "hub".to_string(),
+                exit: true,
+            }
+        );
+        assert_eq!(
+            runner.drain_events(),
+            [DialogueEvent::AddQuest {
+                script: "Quest".to_string(),
+                name: "The quest".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn topics_return_to_the_hub() {
+        let scripts = scripts();
+        let mut runner = DialogueRunner::new();
+        to_hub(&mut runner, &scripts);
+        let items: Vec<String> = runner.topic_items().into_iter().map(|item| item.item).collect();
+        assert_eq!(items, ["Talk"]);
+
+        runner.handle_input(&scripts, DialogueInput::PickTopic("Nothing".to_string()));
+        assert!(matches!(runner.state(), RunnerState::WaitingForTopic { .. }));
+        runner.handle_input(&scripts, DialogueInput::PickTopic("Talk".to_string()));
+        assert_eq!(line(&runner), Some("Talked."));
+        assert_eq!(runner.call_stack().len(), 1);
+
+        runner.handle_input(&scripts, DialogueInput::Continue);
+        assert!(matches!(runner.state(), RunnerState::WaitingForTopic { .. }));
+        assert!(runner.call_stack().is_empty());
+
+        runner.handle_input(&scripts, DialogueInput::LeaveTopics);
+        assert_eq!(line(&runner), Some("Bye."));
+        runner.handle_input(&scripts, DialogueInput::Continue);
+        assert!(runner.is_finished());
+        assert_eq!(runner.position(), None);
+    }
+
+    #[test]
+    fn rollback_undoes_sets_and_quests() {
+        let scripts = scripts();
+        let mut runner = DialogueRunner::new();
+        to_hub(&mut runner, &scripts);
+        runner.handle_input(&scripts, DialogueInput::PickTopic("Talk".to_string()));
+        assert!(runner.quests().get("Quest").is_some());
+        // Hello, the choice, Waiting and Talked.
+        assert_eq!(runner.rollback_depth(), 3);
+
+        assert!(runner.rollback(&scripts, 1));
+        assert_eq!(line(&runner), Some("Waiting."));
+        assert!(runner.quests().get("Quest").is_none());
+        assert!(!runner.topics().contains("hub", "Talk"));
+        assert!(runner.call_stack().is_empty());
+
+        assert!(runner.rollback(&scripts, 2));
+        assert_eq!(line(&runner), Some("Hello."));
+        assert_eq!(runner.variables().get("visits"), None);
+        assert_eq!(runner.rollback_depth(), 0);
+        assert!(!runner.rollback(&scripts, 1));
+
+        // The same way again ends up in the same place.
+        runner.handle_input(&scripts, DialogueInput::Continue);
+        runner.handle_input(&scripts, DialogueInput::Choose(0));
+        assert_eq!(line(&runner), Some("Waiting."));
+        assert_eq!(runner.variables().get("visits"), Some(&Value::Number(1.0)));
+    }
+
+    #[test]
+    fn rollback_after_loading() {
+        let scripts = scripts();
+        let mut runner = DialogueRunner::new();
+        runner.start(&scripts, "Start");
+        let save = SaveGame::capture(&runner);
+
+        let mut loaded = DialogueRunner::new();
+        save.apply(&mut loaded, &scripts).unwrap();
+        assert_eq!(line(&loaded), Some("Hello."));
+        assert_eq!(loaded.history().len(), 1);
+        loaded.handle_input(&scripts, DialogueInput::Continue);
+        assert_eq!(loaded.rollback_depth(), 1);
+
+        assert!(loaded.rollback(&scripts, 1));
+        assert_eq!(line(&loaded), Some("Hello."));
+        assert_eq!(loaded.history().len(), 1);
+    }
+
+    #[test]
+    fn rollback_limit() {
+        let scripts = scripts();
+        let mut runner = DialogueRunner::new();
+        runner.set_rollback_limit(1);
+        to_hub(&mut runner, &scripts);
+        assert_eq!(runner.rollback_depth(), 1);
+        assert!(!runner.rollback(&scripts, 2));
+        assert!(runner.rollback(&scripts, 1));
+        assert!(matches!(runner.state(), RunnerState::WaitingForChoice(_)));
+
+        runner.set_rollback_limit(0);
+        runner.handle_input(&scripts, DialogueInput::Choose(0));
+        assert_eq!(runner.rollback_depth(), 0);
+        assert!(!runner.rollback(&scripts, 1));
+    }
+}
@@ -0,0 +1,344 @@
+//! Save games.
+//!
+//! A save is a pretty-printed JSON document so it can be read and fixed by
+//! hand. Desktop builds keep one file per slot in `saves/`, the browser build
+//! keeps them in local storage.
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    runner::{DialogueRunner, SavedRunner},
+    script::Scripts,
+    ScriptPos,
+};
+
+/// Bump whenever `SaveGame` changes in a way older saves can't be read with.
+pub const SAVE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SaveGame {
+    pub version: u32,
+    #[serde(flatten)]
+    pub runner: SavedRunner,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveError {
+    Io(String),
+    Parse(String),
+    UnsupportedVersion(u32),
+    InvalidSlotName(String),
+    /// The save refers to a script that isn't in the loaded `Scripts`.
+    MissingScript(String),
+    /// The save stopped on an item the loaded script doesn't have anymore.
+    InvalidPosition(ScriptPos),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "couldn't access save: {}", err),
+            SaveError::Parse(err) => write!(f, "save is corrupt: {}", err),
+            SaveError::UnsupportedVersion(version) => write!(
+                f,
+                "save version {} isn't supported, expected {}",
+                version, SAVE_VERSION
+            ),
+            SaveError::InvalidSlotName(slot) => write!(
+                f,
+                "slot name {:?} may only contain letters, digits, '-' and '_'",
+                slot
+            ),
+            SaveError::MissingScript(script) => {
+                write!(f, "save refers to script {} which doesn't exist", script)
+            }
+            SaveError::InvalidPosition(pos) => write!(
+                f,
+                "save stopped at {} @{} which isn't a line, choice, topic hub or action",
+                pos.script, pos.index
+            ),
+        }
+    }
+}
+
+impl SaveGame {
+    pub fn capture(runner: &DialogueRunner) -> Self {
+        Self {
+            version: SAVE_VERSION,
+            runner: runner.save(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, SaveError> {
+        serde_jsonrc::to_string_pretty(self).map_err(|err| SaveError::Parse(err.to_string()))
+    }
+
+    pub fn from_json(data: &str) -> Result<Self, SaveError> {
+        let save = serde_jsonrc::from_str::<SaveGame>(data)
+            .map_err(|err| SaveError::Parse(err.to_string()))?;
+        if save.version != SAVE_VERSION {
+            return Err(SaveError::UnsupportedVersion(save.version));
+        }
+        Ok(save)
+    }
+
+    /// Checks the version and every position in the save against `scripts`,
+    /// so a save made before the scripts were edited fails to load instead of
+    /// misbehaving.
+    pub fn validate(&self, scripts: &Scripts) -> Result<(), SaveError> {
+        if self.version != SAVE_VERSION {
+            return Err(SaveError::UnsupportedVersion(self.version));
+        }
+        for pos in self.runner.position.iter().chain(self.runner.call_stack.iter()) {
+            if !scripts.has_script(&pos.script) {
+                return Err(SaveError::MissingScript(pos.script.clone()));
+            }
+            if !DialogueRunner::can_stop_at(scripts, pos) {
+                return Err(SaveError::InvalidPosition(pos.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn apply(self, runner: &mut DialogueRunner, scripts: &Scripts) -> Result<(), SaveError> {
+        self.validate(scripts)?;
+        runner.restore(scripts, self.runner);
+        Ok(())
+    }
+}
+
+fn check_slot_name(slot: &str) -> Result<(), SaveError> {
+    let valid = !slot.is_empty()
+        && slot
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(SaveError::InvalidSlotName(slot.to_string()))
+    }
+}
+
+pub fn write_slot(slot: &str, save: &SaveGame) -> Result<(), SaveError> {
+    check_slot_name(slot)?;
+    storage::write(slot, &save.to_json()?)
+}
+
+pub fn read_slot(slot: &str) -> Result<SaveGame, SaveError> {
+    check_slot_name(slot)?;
+    SaveGame::from_json(&storage::read(slot)?)
+}
+
+/// Writes a file that isn't a save slot next to the saves, e.g. the seen
+/// lines. `name` includes the extension.
+pub(crate) fn write_file(name: &str, data: &str) -> Result<(), SaveError> {
+    storage::write_file(name, data)
+}
+
+pub(crate) fn read_file(name: &str) -> Result<String, SaveError> {
+    storage::read_file(name)
+}
+
+/// Names of all slots that hold a save, sorted.
+pub fn list_slots() -> Vec<String> {
+    let mut slots = storage::list();
+    slots.sort();
+    slots
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{
+        expr::Value,
+        runner::{DialogueInput, RunnerState},
+        screenplay::parse_screenplay,
+    };
+
+    fn scripts() -> Scripts {
+        parse_screenplay(
+            "== Start ==\nNarrator: Hello.\n@set visits = 1\n@add_quest Quest The quest\nNarrator: Again.\n\n== Quest ==\nNarrator: Quest.\n",
+            Path::new("start.screenplay"),
+        )
+        .unwrap()
+    }
+
+    fn saved_runner(scripts: &Scripts) -> DialogueRunner {
+        let mut runner = DialogueRunner::new();
+        runner.start(scripts, "Start");
+        runner.handle_input(scripts, DialogueInput::Continue);
+        runner
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let scripts = scripts();
+        let runner = saved_runner(&scripts);
+        let json = SaveGame::capture(&runner).to_json().unwrap();
+        let save = SaveGame::from_json(&json).unwrap();
+        assert_eq!(save.to_json().unwrap(), json);
+
+        let mut loaded = DialogueRunner::new();
+        save.apply(&mut loaded, &scripts).unwrap();
+        assert_eq!(loaded.position(), runner.position());
+        assert_eq!(loaded.variables().get("visits"), Some(&Value::Number(1.0)));
+        assert!(loaded.quests().get("Quest").is_some());
+        assert!(loaded.history().iter().eq(runner.history().iter()));
+        assert!(matches!(loaded.state(), RunnerState::ShowingLine(line) if line.text == "Again."));
+    }
+
+    #[test]
+    fn wrong_version() {
+        let scripts = scripts();
+        let mut save = SaveGame::capture(&saved_runner(&scripts));
+        save.version = SAVE_VERSION + 1;
+        assert_eq!(save.validate(&scripts), Err(SaveError::UnsupportedVersion(SAVE_VERSION + 1)));
+        let json = save.to_json().unwrap();
+        assert_eq!(
+            SaveGame::from_json(&json).unwrap_err(),
+            SaveError::UnsupportedVersion(SAVE_VERSION + 1)
+        );
+    }
+
+    #[test]
+    fn positions_outside_the_scripts() {
+        let scripts = scripts();
+        let save = SaveGame::capture(&saved_runner(&scripts));
+
+        let mut past_the_end = save.clone();
+        let pos = past_the_end.runner.position.as_mut().unwrap();
+        pos.index = 10;
+        let expected = SaveError::InvalidPosition(pos.clone());
+        assert_eq!(past_the_end.validate(&scripts), Err(expected));
+
+        // A `set` isn't somewhere the runner stops.
+        let mut not_a_stop = save.clone();
+        not_a_stop.runner.position.as_mut().unwrap().index = 1;
+        assert!(matches!(not_a_stop.validate(&scripts), Err(SaveError::InvalidPosition(_))));
+
+        let mut missing = save;
+        missing.runner.call_stack.push(ScriptPos {
+            script: "Gone".to_string(),
+            index: 0,
+        });
+        assert_eq!(missing.validate(&scripts), Err(SaveError::MissingScript("Gone".to_string())));
+        let mut runner = DialogueRunner::new();
+        assert!(missing.apply(&mut runner, &scripts).is_err());
+        assert!(runner.is_finished());
+    }
+
+    #[test]
+    fn save_without_history() {
+        let scripts = scripts();
+        let json = SaveGame::capture(&saved_runner(&scripts)).to_json().unwrap();
+        let mut data: serde_jsonrc::Value = serde_jsonrc::from_str(&json).unwrap();
+        data.as_object_mut().unwrap().remove("history");
+        let save = SaveGame::from_json(&data.to_string()).unwrap();
+        assert!(save.runner.history.is_empty());
+
+        let mut runner = DialogueRunner::new();
+        save.apply(&mut runner, &scripts).unwrap();
+        // The line it stopped at goes into the history when it's shown again.
+        assert_eq!(runner.history().len(), 1);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod storage {
+    use std::{fs, path::PathBuf};
+
+    use super::SaveError;
+
+    const SAVE_DIR: &str = "saves";
+
+    fn slot_path(slot: &str) -> PathBuf {
+        PathBuf::from(SAVE_DIR).join(format!("{}.json", slot))
+    }
+
+    pub fn write(slot: &str, data: &str) -> Result<(), SaveError> {
+        fs::create_dir_all(SAVE_DIR).map_err(|err| SaveError::Io(err.to_string()))?;
+        fs::write(slot_path(slot), data).map_err(|err| SaveError::Io(err.to_string()))
+    }
+
+    pub fn read(slot: &str) -> Result<String, SaveError> {
+        fs::read_to_string(slot_path(slot)).map_err(|err| SaveError::Io(err.to_string()))
+    }
+
+    pub fn write_file(name: &str, data: &str) -> Result<(), SaveError> {
+        fs::create_dir_all(SAVE_DIR).map_err(|err| SaveError::Io(err.to_string()))?;
+        fs::write(PathBuf::from(SAVE_DIR).join(name), data).map_err(|err| SaveError::Io(err.to_string()))
+    }
+
+    pub fn read_file(name: &str) -> Result<String, SaveError> {
+        fs::read_to_string(PathBuf::from(SAVE_DIR).join(name)).map_err(|err| SaveError::Io(err.to_string()))
+    }
+
+    pub fn list() -> Vec<String> {
+        let Ok(entries) = fs::read_dir(SAVE_DIR) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "json" {
+                    return None;
+                }
+                Some(path.file_stem()?.to_str()?.to_string())
+            })
+            .collect()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod storage {
+    use super::SaveError;
+
+    const KEY_PREFIX: &str = "bleeping_fyrox.save.";
+    const FILE_KEY_PREFIX: &str = "bleeping_fyrox.file.";
+
+    fn local_storage() -> Result<web_sys::Storage, SaveError> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or_else(|| SaveError::Io("local storage isn't available".to_string()))
+    }
+
+    pub fn write(slot: &str, data: &str) -> Result<(), SaveError> {
+        local_storage()?
+            .set_item(&format!("{}{}", KEY_PREFIX, slot), data)
+            .map_err(|err| SaveError::Io(format!("{:?}", err)))
+    }
+
+    pub fn read(slot: &str) -> Result<String, SaveError> {
+        local_storage()?
+            .get_item(&format!("{}{}", KEY_PREFIX, slot))
+            .map_err(|err| SaveError::Io(format!("{:?}", err)))?
+            .ok_or_else(|| SaveError::Io(format!("slot {} is empty", slot)))
+    }
+
+    pub fn write_file(name: &str, data: &str) -> Result<(), SaveError> {
+        local_storage()?
+            .set_item(&format!("{}{}", FILE_KEY_PREFIX, name), data)
+            .map_err(|err| SaveError::Io(format!("{:?}", err)))
+    }
+
+    pub fn read_file(name: &str) -> Result<String, SaveError> {
+        local_storage()?
+            .get_item(&format!("{}{}", FILE_KEY_PREFIX, name))
+            .map_err(|err| SaveError::Io(format!("{:?}", err)))?
+            .ok_or_else(|| SaveError::Io(format!("{} doesn't exist", name)))
+    }
+
+    pub fn list() -> Vec<String> {
+        let Ok(storage) = local_storage() else {
+            return Vec::new();
+        };
+        let length = storage.length().unwrap_or(0);
+        (0..length)
+            .filter_map(|index| storage.key(index).ok().flatten())
+            .filter_map(|key| key.strip_prefix(KEY_PREFIX).map(str::to_string))
+            .collect()
+    }
+}
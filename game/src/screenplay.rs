@@ -0,0 +1,638 @@
+//! A line based screenplay format for scripts, nicer to write by hand than the
+//! JSON one. `.screenplay` files compile to the same `ScriptItem`s and can be
+//! listed in a manifest next to `.dialogue` files.
+//!
+//! ```text
+//! == Prologue ==
+//! # Comments like this one are dropped.
+//! Narrator: The sun rises.
+//! Waxworth (smiling) [2.5]: You see...
+//! % A note for other writers about the line above.
+//! @cue prologue1
+//! * Of course! -> PharaohWhereIsIt
+//! * Ask about the seal -> PharaohRiverSealKa if visits > 1
+//! * Leave -> PharaohDoNotDisturb if? has_seal
+//! -> PharaohOasis
+//! ```
+//!
+//! A line is one item:
+//!
+//! - `Character (annotation) [wait]: text` is a blurp, annotation and wait are
+//!   optional.
+//! - `-> Script` jumps.
+//! - `* text -> Script` is a choice option, options on consecutive lines make
+//!   up one choice. `if condition` after the target hides the option while the
+//!   condition is false, `if? condition` greys it out instead. `@choice`
+//!   starts a choice explicitly, which is needed for one that directly follows
+//!   another.
+//! - `@action`, `@animation`, `@cue`, `@end`, `@complete_quest`, `@fail_quest`
+//!   and `@set` take the rest of the line.
+//! - `@add_quest script name`, `@offer_topics topic [exit]`,
+//!   `@add_topic_item topic item [if condition | if? condition]`,
+//!   `@remove_topic_item topic item` and
+//!   `@if condition -> script [else script]` cover the remaining items.
+//! - `% note` sets the note of the item or option above it.
+//! - `@id name` sets the string table id of the blurp or option above it.
+//!
+//! Anything that would otherwise be cut short or misread, like text with
+//! leading spaces or a name with spaces in it, is written as a JSON string.
+use std::{fmt::Write, path::Path};
+
+use crate::{
+    location::{Location, ScriptError, ScriptLocations},
+    script::{
+        Action, AddQuest, AddTopicItem, Animation, Blurp, Choice, ChoiceOption, CompleteQuest,
+        Cue, End, FailQuest, If, Jump, OfferTopics, RemoveTopicItem, ScriptItem, Scripts, Set,
+    },
+};
+
+pub const SCREENPLAY_EXTENSION: &str = "screenplay";
+
+/// Characters that start something other than a blurp when they begin a line.
+const LINE_MARKERS: &[&str] = &["#", "%", "==", "->", "*", "@"];
+
+/// What a `%` note line belongs to.
+enum Last {
+    Nothing,
+    Item,
+    Option,
+}
+
+struct Parser<'a> {
+    file: &'a Path,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: impl Into<String>) -> ScriptError {
+        ScriptError::Parse {
+            location: Location::new(self.file, self.line, 1),
+            message: message.into(),
+        }
+    }
+
+    /// Reads a JSON string literal from the start of `text`, returning it and
+    /// what follows it.
+    fn quoted<'t>(&self, text: &'t str) -> Result<(String, &'t str), ScriptError> {
+        let mut escaped = false;
+        for (index, c) in text.char_indices().skip(1) {
+            match c {
+                '\\' if !escaped => escaped = true,
+                '"' if !escaped => {
+                    let value = serde_jsonrc::from_str::<String>(&text[..=index])
+                        .map_err(|err| self.error(format!("bad quoted text: {}", err)))?;
+                    return Ok((value, &text[index + 1..]));
+                }
+                _ => escaped = false,
+            }
+        }
+        Err(self.error("quoted text is missing its closing \""))
+    }
+
+    /// A value that takes the rest of the line.
+    fn rest(&self, text: &str) -> Result<String, ScriptError> {
+        let text = text.trim();
+        if !text.starts_with('"') {
+            return Ok(text.to_string());
+        }
+        let (value, after) = self.quoted(text)?;
+        if !after.trim().is_empty() {
+            return Err(self.error(format!("unexpected {:?} after quoted text", after.trim())));
+        }
+        Ok(value)
+    }
+
+    /// A value that ends where `delimiter` starts, returned with the text from
+    /// `delimiter` on, or `None` for it if there's no `delimiter`.
+    fn until<'t>(&self, text: &'t str, delimiter: &str) -> Result<(String, Option<&'t str>), ScriptError> {
+        let text = text.trim_start();
+        if text.starts_with('"') {
+            let (value, after) = self.quoted(text)?;
+            let after = after.trim_start();
+            return match after.is_empty() {
+                true => Ok((value, None)),
+                false if after.starts_with(delimiter) => Ok((value, Some(after))),
+                false => Err(self.error(format!("expected {} after quoted text", delimiter))),
+            };
+        }
+        match text.find(delimiter) {
+            Some(index) => Ok((text[..index].trim_end().to_string(), Some(&text[index..]))),
+            None => Ok((text.trim_end().to_string(), None)),
+        }
+    }
+
+    /// A single word, or quoted text, returned with what follows it.
+    fn word<'t>(&self, text: &'t str, what: &str) -> Result<(String, &'t str), ScriptError> {
+        let text = text.trim_start();
+        if text.starts_with('"') {
+            return self.quoted(text);
+        }
+        let end = text.find(char::is_whitespace).unwrap_or(text.len());
+        if end == 0 {
+            return Err(self.error(format!("expected {}", what)));
+        }
+        Ok((text[..end].to_string(), &text[end..]))
+    }
+
+    fn end_of_line(&self, text: &str) -> Result<(), ScriptError> {
+        match text.trim() {
+            "" => Ok(()),
+            extra => Err(self.error(format!("unexpected {:?} at the end of the line", extra))),
+        }
+    }
+
+    /// An optional `if condition` or `if? condition`, as the condition and
+    /// whether to show the thing disabled.
+    fn condition(&self, text: &str) -> Result<(Option<String>, bool), ScriptError> {
+        let text = text.trim();
+        if let Some(condition) = text.strip_prefix("if?") {
+            if condition.trim().is_empty() {
+                return Ok((None, true));
+            }
+            return Ok((Some(self.rest(condition)?), true));
+        }
+        if let Some(condition) = text.strip_prefix("if ") {
+            return Ok((Some(self.rest(condition)?), false));
+        }
+        self.end_of_line(text)?;
+        Ok((None, false))
+    }
+
+    fn blurp(&self, line: &str) -> Result<Blurp, ScriptError> {
+        let (character, mut after) = if line.starts_with('"') {
+            self.quoted(line)?
+        } else {
+            let end = line.find([':', '(', '[']).unwrap_or(line.len());
+            (line[..end].trim().to_string(), &line[end..])
+        };
+        let mut blurp = Blurp {
+            character,
+            ..Default::default()
+        };
+        after = after.trim_start();
+        if let Some(rest) = after.strip_prefix('(') {
+            let (annotation, rest) = self.until(rest, ")")?;
+            let Some(rest) = rest else {
+                return Err(self.error("annotation is missing its closing )"));
+            };
+            blurp.annotation = Some(annotation);
+            after = rest[1..].trim_start();
+        }
+        if let Some(rest) = after.strip_prefix('[') {
+            let Some(end) = rest.find(']') else {
+                return Err(self.error("wait is missing its closing ]"));
+            };
+            let wait = rest[..end].trim();
+            blurp.wait = Some(
+                wait.parse()
+                    .map_err(|_| self.error(format!("wait {:?} isn't a number", wait)))?,
+            );
+            after = rest[end + 1..].trim_start();
+        }
+        let Some(text) = after.strip_prefix(':') else {
+            return Err(self.error("expected `Character: text` or a line starting with ==, ->, *, @, % or #"));
+        };
+        blurp.text = self.rest(text)?;
+        Ok(blurp)
+    }
+
+    fn option(&self, line: &str) -> Result<ChoiceOption, ScriptError> {
+        let (text, rest) = self.until(line, "->")?;
+        let Some(rest) = rest else {
+            return Err(self.error("choice option needs `-> Script`"));
+        };
+        let (jump, rest) = self.word(&rest[2..], "a script to jump to")?;
+        let (condition, show_disabled) = self.condition(rest)?;
+        Ok(ChoiceOption {
+            jump,
+            text,
+            condition,
+            show_disabled,
+            id: None,
+            note: None,
+        })
+    }
+
+    fn directive(&self, line: &str) -> Result<ScriptItem, ScriptError> {
+        let (name, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        Ok(match name {
+            "action" => ScriptItem::Action(Action {
+                action: self.rest(rest)?,
+                note: None,
+            }),
+            "add_quest" => {
+                let (script, rest) = self.word(rest, "the quest's script")?;
+                ScriptItem::AddQuest(AddQuest {
+                    add_quest_script: script,
+                    add_quest_name: self.rest(rest)?,
+                    note: None,
+                })
+            }
+            "add_topic_item" => {
+                let (topic, rest) = self.word(rest, "a topic")?;
+                let (item, rest) = self.word(rest, "an item")?;
+                let (condition, show_disabled) = self.condition(rest)?;
+                ScriptItem::AddTopicItem(AddTopicItem {
+                    add_topic_item: topic,
+                    item,
+                    condition,
+                    show_disabled,
+                    note: None,
+                })
+            }
+            "animation" => ScriptItem::Animation(Animation {
+                animation: self.rest(rest)?,
+                note: None,
+            }),
+            "choice" => {
+                self.end_of_line(rest)?;
+                ScriptItem::Choice(Choice::default())
+            }
+            "complete_quest" => ScriptItem::CompleteQuest(CompleteQuest {
+                complete_quest: self.rest(rest)?,
+                note: None,
+            }),
+            "cue" => ScriptItem::Cue(Cue {
+                cue: self.rest(rest)?,
+                note: None,
+            }),
+            "end" => ScriptItem::End(End {
+                end: self.rest(rest)?,
+                note: None,
+            }),
+            "fail_quest" => ScriptItem::FailQuest(FailQuest {
+                fail_quest: self.rest(rest)?,
+                note: None,
+            }),
+            "if" => {
+                let (condition, rest) = self.until(rest, "->")?;
+                let Some(rest) = rest else {
+                    return Err(self.error("@if needs `-> Script`"));
+                };
+                let (jump, rest) = self.word(&rest[2..], "a script to jump to")?;
+                let rest = rest.trim();
+                let else_jump = match rest.strip_prefix("else") {
+                    Some(rest) => {
+                        let (target, rest) = self.word(rest, "a script to jump to")?;
+                        self.end_of_line(rest)?;
+                        Some(target)
+                    }
+                    None => {
+                        self.end_of_line(rest)?;
+                        None
+                    }
+                };
+                ScriptItem::If(If {
+                    condition,
+                    jump,
+                    else_jump,
+                    note: None,
+                })
+            }
+            "offer_topics" => {
+                let (topic, rest) = self.word(rest, "a topic")?;
+                let exit = match rest.trim() {
+                    "exit" => true,
+                    rest => {
+                        self.end_of_line(rest)?;
+                        false
+                    }
+                };
+                ScriptItem::OfferTopics(OfferTopics {
+                    offer_topics: topic,
+                    exit,
+                    note: None,
+                })
+            }
+            "remove_topic_item" => {
+                let (topic, rest) = self.word(rest, "a topic")?;
+                let (item, rest) = self.word(rest, "an item")?;
+                self.end_of_line(rest)?;
+                ScriptItem::RemoveTopicItem(RemoveTopicItem {
+                    remove_topic_item: topic,
+                    item,
+                    note: None,
+                })
+            }
+            "set" => ScriptItem::Set(Set {
+                set: self.rest(rest)?,
+                note: None,
+            }),
+            _ => return Err(self.error(format!("unknown directive @{}", name))),
+        })
+    }
+}
+
+struct ParsedScript {
+    name: String,
+    items: Vec<ScriptItem>,
+    locations: ScriptLocations,
+}
+
+/// Compiles a screenplay into `Scripts`, `file` is only used for locations.
+pub fn parse_screenplay(data: &str, file: &Path) -> Result<Scripts, ScriptError> {
+    let mut parser = Parser { file, line: 0 };
+    let mut parsed: Vec<ParsedScript> = Vec::new();
+    // Whether `*` lines still add options to the last item.
+    let mut open_choice = false;
+    let mut last = Last::Nothing;
+    for (index, line) in data.lines().enumerate() {
+        parser.line = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix("==") {
+            let (name, rest) = parser.word(header, "a script name")?;
+            let rest = rest.trim();
+            parser.end_of_line(rest.strip_suffix("==").unwrap_or(rest))?;
+            if parsed.iter().any(|script| script.name == name) {
+                return Err(parser.error(format!("script {} is defined twice", name)));
+            }
+            parsed.push(ParsedScript {
+                name,
+                items: Vec::new(),
+                locations: ScriptLocations {
+                    script: Location::new(file, parser.line, 1),
+                    items: Vec::new(),
+                },
+            });
+            open_choice = false;
+            last = Last::Nothing;
+            continue;
+        }
+        let Some(script) = parsed.last_mut() else {
+            return Err(parser.error("expected a `== Script ==` line before the first item"));
+        };
+        if let Some(id) = line
+            .strip_prefix("@id")
+            .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            let (id, rest) = parser.word(id, "a string id")?;
+            parser.end_of_line(rest)?;
+            let target = match (&last, script.items.last_mut()) {
+                (Last::Option, Some(ScriptItem::Choice(choice))) => {
+                    choice.choice.last_mut().map(|option| &mut option.id)
+                }
+                (Last::Item, Some(ScriptItem::Blurp(blurp))) => Some(&mut blurp.id),
+                _ => None,
+            };
+            let Some(target) = target else {
+                return Err(parser.error("a string id has to follow a blurp or option"));
+            };
+            if target.is_some() {
+                return Err(parser.error("only one string id per blurp or option"));
+            }
+            *target = Some(id);
+            continue;
+        }
+        if let Some(note) = line.strip_prefix('%') {
+            let note = Some(parser.rest(note)?);
+            let target = match (&last, script.items.last_mut()) {
+                (Last::Option, Some(ScriptItem::Choice(choice))) => match choice.choice.last_mut() {
+                    Some(option) => &mut option.note,
+                    None => return Err(parser.error("a note has to follow an item or option")),
+                },
+                (Last::Item, Some(item)) => item.note_mut(),
+                _ => return Err(parser.error("a note has to follow an item or option")),
+            };
+            if target.is_some() {
+                return Err(parser.error("only one note per item or option"));
+            }
+            *target = note;
+            continue;
+        }
+        if let Some(option) = line.strip_prefix('*') {
+            let option = parser.option(option)?;
+            if !open_choice {
+                script.items.push(ScriptItem::Choice(Choice::default()));
+                script.locations.items.push(Location::new(file, parser.line, 1));
+                open_choice = true;
+            }
+            if let Some(ScriptItem::Choice(choice)) = script.items.last_mut() {
+                choice.choice.push(option);
+            }
+            last = Last::Option;
+            continue;
+        }
+        let item = if let Some(target) = line.strip_prefix("->") {
+            let (jump, rest) = parser.word(target, "a script to jump to")?;
+            parser.end_of_line(rest)?;
+            ScriptItem::Jump(Jump { jump, note: None })
+        } else if let Some(directive) = line.strip_prefix('@') {
+            parser.directive(directive)?
+        } else {
+            ScriptItem::Blurp(parser.blurp(line)?)
+        };
+        open_choice = matches!(item, ScriptItem::Choice(_));
+        last = Last::Item;
+        script.items.push(item);
+        script.locations.items.push(Location::new(file, parser.line, 1));
+    }
+    let mut scripts = Scripts::default();
+    for script in parsed {
+        scripts.insert(script.name.clone(), script.items);
+        scripts.set_locations(&script.name, script.locations);
+    }
+    Ok(scripts)
+}
+
+fn quote(text: &str) -> String {
+    serde_jsonrc::to_string(text).unwrap_or_else(|_| format!("{:?}", text))
+}
+
+/// `text` as written where it runs until the end of the line or one of
+/// `delimiters`.
+fn field(text: &str, delimiters: &[&str]) -> String {
+    let plain = text == text.trim()
+        && !text.starts_with('"')
+        && !text.chars().any(char::is_control)
+        && !delimiters.iter().any(|delimiter| text.contains(delimiter));
+    if plain {
+        text.to_string()
+    } else {
+        quote(text)
+    }
+}
+
+/// `text` as written where a single word is expected.
+fn word(text: &str) -> String {
+    if text.is_empty() || text.starts_with('"') || text.contains(|c: char| c.is_whitespace() || c.is_control()) {
+        quote(text)
+    } else {
+        text.to_string()
+    }
+}
+
+fn condition(condition: &Option<String>, show_disabled: bool) -> String {
+    // An empty condition is quoted, a bare `if?` would read back as none.
+    let written = |condition: &str| {
+        if condition.is_empty() {
+            quote(condition)
+        } else {
+            field(condition, &[])
+        }
+    };
+    match (condition, show_disabled) {
+        (Some(condition), false) => format!(" if {}", written(condition)),
+        (Some(condition), true) => format!(" if? {}", written(condition)),
+        (None, true) => " if?".to_string(),
+        (None, false) => String::new(),
+    }
+}
+
+fn write_id(out: &mut String, id: Option<&str>) {
+    if let Some(id) = id {
+        let _ = writeln!(out, "@id {}", word(id));
+    }
+}
+
+fn write_note(out: &mut String, note: Option<&str>) {
+    if let Some(note) = note {
+        let _ = writeln!(out, "% {}", field(note, &[]));
+    }
+}
+
+fn write_item(out: &mut String, item: &ScriptItem, after_choice: bool) {
+    let line = match item {
+        ScriptItem::Action(action) => format!("@action {}", field(&action.action, &[])),
+        ScriptItem::AddQuest(quest) => format!(
+            "@add_quest {} {}",
+            word(&quest.add_quest_script),
+            field(&quest.add_quest_name, &[])
+        ),
+        ScriptItem::AddTopicItem(add) => format!(
+            "@add_topic_item {} {}{}",
+            word(&add.add_topic_item),
+            word(&add.item),
+            condition(&add.condition, add.show_disabled)
+        ),
+        ScriptItem::Animation(animation) => format!("@animation {}", field(&animation.animation, &[])),
+        ScriptItem::Blurp(blurp) => {
+            let character = &blurp.character;
+            let mut line = if character.is_empty() || LINE_MARKERS.iter().any(|marker| character.starts_with(marker)) {
+                quote(character)
+            } else {
+                field(character, &[":", "(", "["])
+            };
+            if let Some(annotation) = &blurp.annotation {
+                let _ = write!(line, " ({})", field(annotation, &[")"]));
+            }
+            if let Some(wait) = blurp.wait {
+                let _ = write!(line, " [{}]", wait);
+            }
+            let _ = write!(line, ": {}", field(&blurp.text, &[]));
+            line.trim_end().to_string()
+        }
+        ScriptItem::Choice(choice) => {
+            // Options are only gathered into a new choice when they don't
+            // follow another one and there's nothing to say about the
+            // choice itself.
+            if after_choice || choice.choice.is_empty() || choice.note.is_some() {
+                let _ = writeln!(out, "@choice");
+                write_note(out, choice.note.as_deref());
+            }
+            for option in &choice.choice {
+                let _ = writeln!(
+                    out,
+                    "* {} -> {}{}",
+                    field(&option.text, &["->"]),
+                    word(&option.jump),
+                    condition(&option.condition, option.show_disabled)
+                );
+                write_id(out, option.id.as_deref());
+                write_note(out, option.note.as_deref());
+            }
+            return;
+        }
+        ScriptItem::CompleteQuest(quest) => format!("@complete_quest {}", field(&quest.complete_quest, &[])),
+        ScriptItem::Cue(cue) => format!("@cue {}", field(&cue.cue, &[])),
+        ScriptItem::End(end) => format!("@end {}", field(&end.end, &[])).trim_end().to_string(),
+        ScriptItem::FailQuest(quest) => format!("@fail_quest {}", field(&quest.fail_quest, &[])),
+        ScriptItem::If(branch) => {
+            let mut line = format!(
+                "@if {} -> {}",
+                field(&branch.condition, &["->"]),
+                word(&branch.jump)
+            );
+            if let Some(else_jump) = &branch.else_jump {
+                let _ = write!(line, " else {}", word(else_jump));
+            }
+            line
+        }
+        ScriptItem::Jump(jump) => format!("-> {}", word(&jump.jump)),
+        ScriptItem::OfferTopics(offer) => {
+            let exit = if offer.exit { " exit" } else { "" };
+            format!("@offer_topics {}{}", word(&offer.offer_topics), exit)
+        }
+        ScriptItem::RemoveTopicItem(remove) => format!(
+            "@remove_topic_item {} {}",
+            word(&remove.remove_topic_item),
+            word(&remove.item)
+        ),
+        ScriptItem::Set(set) => format!("@set {}", field(&set.set, &[])),
+    };
+    let _ = writeln!(out, "{}", line);
+    if let ScriptItem::Blurp(blurp) = item {
+        write_id(out, blurp.id.as_deref());
+    }
+    write_note(out, item.note());
+}
+
+/// Writes `scripts` as a screenplay that parses back into the same scripts.
+/// Scripts keep the order they were written in when their locations are
+/// known and are sorted by name otherwise.
+pub fn export_screenplay(scripts: &Scripts) -> String {
+    let mut names: Vec<&String> = scripts.keys().collect();
+    names.sort_by_key(|name| {
+        let location = scripts.locations(name).map(|locations| &locations.script);
+        (
+            location.map(|location| location.file.clone()),
+            location.map(|location| location.line),
+            name.to_string(),
+        )
+    });
+    let mut out = String::new();
+    for (index, name) in names.into_iter().enumerate() {
+        if index > 0 {
+            out.push('\n');
+        }
+        let _ = writeln!(out, "== {} ==", word(name));
+        let mut after_choice = false;
+        for item in scripts.get(name).into_iter().flatten() {
+            write_item(&mut out, item, after_choice);
+            after_choice = matches!(item, ScriptItem::Choice(_));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_conditions_round_trip() {
+        let screenplay = "== Start ==\n\
+            @add_topic_item hub Start if \"\"\n\
+            * Hidden -> Start if \"\"\n\
+            * Greyed -> Start if? \"\"\n\
+            * Always greyed -> Start if?\n";
+        let scripts = parse_screenplay(screenplay, Path::new("start.screenplay")).unwrap();
+        let Some([ScriptItem::AddTopicItem(add), ScriptItem::Choice(choice)]) =
+            scripts.get("Start").map(Vec::as_slice)
+        else {
+            panic!("unexpected items {:?}", scripts.get("Start"));
+        };
+        assert_eq!(add.condition.as_deref(), Some(""));
+        let conditions: Vec<_> = choice
+            .choice
+            .iter()
+            .map(|option| (option.condition.as_deref(), option.show_disabled))
+            .collect();
+        assert_eq!(conditions, [(Some(""), false), (Some(""), true), (None, true)]);
+
+        let exported = export_screenplay(&scripts);
+        assert_eq!(parse_screenplay(&exported, Path::new("exported.screenplay")).unwrap(), scripts);
+    }
+}
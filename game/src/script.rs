@@ -1,6 +1,13 @@
-use std::{collections::{HashMap, hash_map::Keys}, fs};
+//! Script data as it's written in `data/scripts.dialogue`.
+//!
+//! Every item and choice option can carry a `note` for whoever edits the
+//! scripts. It's kept when scripts are loaded and saved but never shown.
+use std::{collections::{HashMap, hash_map::{Iter, Keys}}, fmt, fs, path::Path};
 
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::location::{scan_locations, Location, ScriptError, ScriptLocations};
+use crate::screenplay::{parse_screenplay, SCREENPLAY_EXTENSION};
 
 use fyrox::{
     core::{reflect::Reflect, visitor::{Visit, VisitResult, Visitor}, reflect::prelude::*,},
@@ -8,91 +15,259 @@ use fyrox::{
 
 
 
-#[derive(Serialize, Deserialize, Debug, Visit, Reflect, Default)]
-pub struct Scripts(HashMap::<String, Vec::<ScriptItem>>);
+#[derive(Debug, Clone, Visit, Reflect, Default)]
+pub struct Scripts {
+    scripts: HashMap<String, Vec<ScriptItem>>,
+    /// Where each script came from, empty for scripts that weren't parsed
+    /// from a file.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    locations: HashMap<String, ScriptLocations>,
+}
 impl Scripts {
     pub fn get(&self, key: &str) -> Option<&Vec<ScriptItem>> {
-        self.0.get(key)
+        self.scripts.get(key)
     }
     pub fn get_item(&self, key: &str, index: usize) -> Option<&ScriptItem> {
-        self.0.get(key)?.get(index)
+        self.scripts.get(key)?.get(index)
     }
     pub fn keys(&self) -> Keys<'_, String, Vec<ScriptItem>> {
-        self.0.keys()
+        self.scripts.keys()
+    }
+    pub fn iter(&self) -> Iter<'_, String, Vec<ScriptItem>> {
+        self.scripts.iter()
     }
     pub fn has_script(&self, script:&str) ->bool {
-        self.0.contains_key(script)
+        self.scripts.contains_key(script)
+    }
+    /// Replaces `script`, forgetting where the old one was defined.
+    pub fn insert(&mut self, script: String, items: Vec<ScriptItem>) -> Option<Vec<ScriptItem>> {
+        self.locations.remove(&script);
+        self.scripts.insert(script, items)
+    }
+    pub fn locations(&self, script: &str) -> Option<&ScriptLocations> {
+        self.locations.get(script)
+    }
+    pub fn set_locations(&mut self, script: &str, locations: ScriptLocations) {
+        self.locations.insert(script.to_string(), locations);
+    }
+    /// Where the item was written, if the scripts came from a file.
+    pub fn location(&self, script: &str, index: usize) -> Option<&Location> {
+        self.locations.get(script)?.items.get(index)
+    }
+    /// `script @index` for logs, followed by the item's location if known.
+    pub fn describe(&self, script: &str, index: usize) -> String {
+        match self.location(script, index) {
+            Some(location) => format!("{} @{} ({})", script, index, location),
+            None => format!("{} @{}", script, index),
+        }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Visit, Reflect, Default)]
+/// Scripts are equal when their items are, wherever they were written.
+impl PartialEq for Scripts {
+    fn eq(&self, other: &Self) -> bool {
+        self.scripts == other.scripts
+    }
+}
+
+impl Serialize for Scripts {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.scripts.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Scripts {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self {
+            scripts: HashMap::deserialize(deserializer)?,
+            locations: HashMap::new(),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Visit, Reflect, Default)]
+#[serde(deny_unknown_fields)]
 pub struct Action {
-    pub action: String
+    pub action: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Visit, Reflect, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Visit, Reflect, Default)]
+#[serde(deny_unknown_fields)]
 pub struct AddQuest {
     pub add_quest_script: String,
 	pub add_quest_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Visit, Reflect, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Visit, Reflect, Default)]
+#[serde(deny_unknown_fields)]
+pub struct AddTopicItem {
+    pub add_topic_item: String,
+    pub item: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+    /// Grey the item out instead of hiding it while `condition` is false.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub show_disabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Visit, Reflect, Default)]
+#[serde(deny_unknown_fields)]
 pub struct Animation {
     pub animation: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Visit, Reflect, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Visit, Reflect, Default)]
+#[serde(deny_unknown_fields)]
 pub struct Blurp {
     pub character: String,
     pub text: String,
     pub annotation: Option<String>,
+    /// Seconds the line stays up in auto mode, instead of its reading time.
     pub wait: Option<f64>,
+    /// String table id of `text`, see `localization`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Visit, Reflect, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Visit, Reflect, Default)]
+#[serde(deny_unknown_fields)]
+pub struct CompleteQuest {
+    pub complete_quest: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Visit, Reflect, Default)]
+#[serde(deny_unknown_fields)]
 pub struct Cue {
-    pub cue: String
+    pub cue: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Visit, Reflect, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Visit, Reflect, Default)]
+#[serde(deny_unknown_fields)]
 pub struct Choice {
-    pub choice: Vec<ChoiceOption>
+    pub choice: Vec<ChoiceOption>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Visit, Reflect, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Visit, Reflect, Default)]
+#[serde(deny_unknown_fields)]
 pub struct ChoiceOption {
     pub jump: String,
     pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+    /// Grey the option out instead of hiding it while `condition` is false.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub show_disabled: bool,
+    /// String table id of `text`, see `localization`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Visit, Reflect, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Visit, Reflect, Default)]
+#[serde(deny_unknown_fields)]
 pub struct End {
-    pub end: String
+    pub end: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Visit, Reflect, Default)]
+#[serde(deny_unknown_fields)]
+pub struct FailQuest {
+    pub fail_quest: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Visit, Reflect, Default)]
+#[serde(deny_unknown_fields)]
+pub struct If {
+    #[serde(rename = "if")]
+    pub condition: String,
+    /// Script to jump to when `condition` is true.
+    pub jump: String,
+    /// Script to jump to otherwise, without one the script just carries on.
+    #[serde(rename = "else", default, skip_serializing_if = "Option::is_none")]
+    pub else_jump: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Visit, Reflect, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Visit, Reflect, Default)]
+#[serde(deny_unknown_fields)]
 pub struct Jump {
-    pub jump: String
+    pub jump: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Visit, Reflect, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Visit, Reflect, Default)]
+#[serde(deny_unknown_fields)]
 pub struct OfferTopics {
     pub offer_topics: String,
-    pub exit: bool
+    pub exit: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Visit, Reflect, Default)]
+#[serde(deny_unknown_fields)]
+pub struct RemoveTopicItem {
+    pub remove_topic_item: String,
+    pub item: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Visit, Reflect, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Set {
+    /// An assignment like `"gold = gold + 5"` or `"visits += 1"`.
+    pub set: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Visit, Reflect)]
+/// Items are written as plain JSON objects and told apart by their
+/// discriminating key (see `ITEM_KINDS`), so deserializing is done by hand
+/// instead of through `#[serde(untagged)]`, which would quietly pick whichever
+/// variant happened to fit and drop any keys it didn't know.
+#[derive(Serialize, Debug, Clone, PartialEq, Visit, Reflect)]
 #[serde(untagged)]
 pub enum ScriptItem {
     Action(Action),
     AddQuest(AddQuest),
+    AddTopicItem(AddTopicItem),
     Animation(Animation),
     Blurp(Blurp),
     Choice(Choice),
+    CompleteQuest(CompleteQuest),
     Cue(Cue),
     End(End),
+    FailQuest(FailQuest),
+    If(If),
     Jump(Jump),
     OfferTopics(OfferTopics),
+    RemoveTopicItem(RemoveTopicItem),
+    Set(Set),
 }
 impl Default for ScriptItem {
     fn default() -> Self {
@@ -100,20 +275,296 @@ impl Default for ScriptItem {
     }
 }
 
-pub fn load_from_file(file_path : &str) -> Result<Scripts, String>{
-    let data = match fs::read_to_string(file_path) {
-        Ok(data) => data,
-        Err(err) => {
-            return Err(format!("{err:?}"));
+impl ScriptItem {
+    pub fn note(&self) -> Option<&str> {
+        let note = match self {
+            ScriptItem::Action(item) => &item.note,
+            ScriptItem::AddQuest(item) => &item.note,
+            ScriptItem::AddTopicItem(item) => &item.note,
+            ScriptItem::Animation(item) => &item.note,
+            ScriptItem::Blurp(item) => &item.note,
+            ScriptItem::Choice(item) => &item.note,
+            ScriptItem::CompleteQuest(item) => &item.note,
+            ScriptItem::Cue(item) => &item.note,
+            ScriptItem::End(item) => &item.note,
+            ScriptItem::FailQuest(item) => &item.note,
+            ScriptItem::If(item) => &item.note,
+            ScriptItem::Jump(item) => &item.note,
+            ScriptItem::OfferTopics(item) => &item.note,
+            ScriptItem::RemoveTopicItem(item) => &item.note,
+            ScriptItem::Set(item) => &item.note,
+        };
+        note.as_deref()
+    }
+
+    pub fn note_mut(&mut self) -> &mut Option<String> {
+        match self {
+            ScriptItem::Action(item) => &mut item.note,
+            ScriptItem::AddQuest(item) => &mut item.note,
+            ScriptItem::AddTopicItem(item) => &mut item.note,
+            ScriptItem::Animation(item) => &mut item.note,
+            ScriptItem::Blurp(item) => &mut item.note,
+            ScriptItem::Choice(item) => &mut item.note,
+            ScriptItem::CompleteQuest(item) => &mut item.note,
+            ScriptItem::Cue(item) => &mut item.note,
+            ScriptItem::End(item) => &mut item.note,
+            ScriptItem::FailQuest(item) => &mut item.note,
+            ScriptItem::If(item) => &mut item.note,
+            ScriptItem::Jump(item) => &mut item.note,
+            ScriptItem::OfferTopics(item) => &mut item.note,
+            ScriptItem::RemoveTopicItem(item) => &mut item.note,
+            ScriptItem::Set(item) => &mut item.note,
+        }
+    }
+}
+
+/// The key that decides what kind of item an object is. `jump` only counts
+/// when there's no `if`, since an `if` item has a `jump` too.
+pub const ITEM_KINDS: &[&str] = &[
+    "action",
+    "add_quest_script",
+    "add_topic_item",
+    "animation",
+    "character",
+    "choice",
+    "complete_quest",
+    "cue",
+    "end",
+    "fail_quest",
+    "if",
+    "jump",
+    "offer_topics",
+    "remove_topic_item",
+    "set",
+];
+
+/// Every key any script item may have.
+pub const ITEM_KEYS: &[&str] = &[
+    "action",
+    "add_quest_name",
+    "add_quest_script",
+    "add_topic_item",
+    "animation",
+    "annotation",
+    "character",
+    "choice",
+    "complete_quest",
+    "condition",
+    "cue",
+    "else",
+    "end",
+    "exit",
+    "fail_quest",
+    "id",
+    "if",
+    "item",
+    "jump",
+    "note",
+    "offer_topics",
+    "remove_topic_item",
+    "set",
+    "show_disabled",
+    "text",
+    "wait",
+];
+
+/// Every key a choice option may have.
+pub const OPTION_KEYS: &[&str] = &["condition", "id", "jump", "note", "show_disabled", "text"];
+
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
         }
-    };
-    
-    match serde_jsonrc::from_str::<Scripts>(&data) {
-        Ok(script) => {
-            return Ok(script);
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The known key `key` is most likely a misspelling of, if any is close.
+pub(crate) fn closest_key<'a>(key: &str, known: &[&'a str]) -> Option<&'a str> {
+    known
+        .iter()
+        .map(|candidate| (edit_distance(key, candidate), *candidate))
+        .min()
+        .filter(|(distance, _)| *distance <= 2)
+        .map(|(_, candidate)| candidate)
+}
+
+fn item_kind(fields: &serde_jsonrc::Map<String, serde_jsonrc::Value>) -> Result<&'static str, String> {
+    let found: Vec<&'static str> = ITEM_KINDS
+        .iter()
+        .copied()
+        .filter(|kind| fields.contains_key(*kind))
+        .filter(|kind| *kind != "jump" || !fields.contains_key("if"))
+        .collect();
+    match found.as_slice() {
+        [kind] => Ok(kind),
+        [] => Err(format!(
+            "item has none of the keys that say what it is, expected one of {}",
+            ITEM_KINDS.join(", ")
+        )),
+        _ => Err(format!("item can only be one kind but has keys {}", found.join(", "))),
+    }
+}
+
+fn item_from_fields(kind: &str, fields: serde_jsonrc::Map<String, serde_jsonrc::Value>) -> Result<ScriptItem, serde_jsonrc::Error> {
+    let value = serde_jsonrc::Value::Object(fields);
+    Ok(match kind {
+        "action" => ScriptItem::Action(serde_jsonrc::from_value(value)?),
+        "add_quest_script" => ScriptItem::AddQuest(serde_jsonrc::from_value(value)?),
+        "add_topic_item" => ScriptItem::AddTopicItem(serde_jsonrc::from_value(value)?),
+        "animation" => ScriptItem::Animation(serde_jsonrc::from_value(value)?),
+        "character" => ScriptItem::Blurp(serde_jsonrc::from_value(value)?),
+        "choice" => ScriptItem::Choice(serde_jsonrc::from_value(value)?),
+        "complete_quest" => ScriptItem::CompleteQuest(serde_jsonrc::from_value(value)?),
+        "cue" => ScriptItem::Cue(serde_jsonrc::from_value(value)?),
+        "end" => ScriptItem::End(serde_jsonrc::from_value(value)?),
+        "fail_quest" => ScriptItem::FailQuest(serde_jsonrc::from_value(value)?),
+        "if" => ScriptItem::If(serde_jsonrc::from_value(value)?),
+        "jump" => ScriptItem::Jump(serde_jsonrc::from_value(value)?),
+        "offer_topics" => ScriptItem::OfferTopics(serde_jsonrc::from_value(value)?),
+        "remove_topic_item" => ScriptItem::RemoveTopicItem(serde_jsonrc::from_value(value)?),
+        "set" => ScriptItem::Set(serde_jsonrc::from_value(value)?),
+        _ => unreachable!("item_kind only returns entries of ITEM_KINDS"),
+    })
+}
+
+struct ScriptItemVisitor;
+
+impl<'de> de::Visitor<'de> for ScriptItemVisitor {
+    type Value = ScriptItem;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a script item object")
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<ScriptItem, A::Error> {
+        let mut fields = serde_jsonrc::Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            // Failing here rather than once the whole item is read puts the
+            // error's line and column on the bad key itself.
+            if !ITEM_KEYS.contains(&key.as_str()) {
+                return Err(de::Error::custom(match closest_key(&key, ITEM_KEYS) {
+                    Some(known) => format!("unknown key `{}`, did you mean `{}`?", key, known),
+                    None => format!("unknown key `{}`", key),
+                }));
+            }
+            if fields.contains_key(&key) {
+                return Err(de::Error::custom(format!("duplicate key `{}`", key)));
+            }
+            let value = map.next_value::<serde_jsonrc::Value>()?;
+            fields.insert(key, value);
         }
-        Err(err) => {
-            return Err(format!("{err:?}"));
+        let kind = item_kind(&fields).map_err(de::Error::custom)?;
+        item_from_fields(kind, fields)
+            .map_err(|err| de::Error::custom(format!("`{}` item: {}", kind, err)))
+    }
+}
+
+impl<'de> Deserialize<'de> for ScriptItem {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(ScriptItemVisitor)
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// Parses a scripts file, `file` is only used for locations.
+pub fn parse_scripts(data: &str, file: &Path) -> Result<Scripts, ScriptError> {
+    let mut scripts = serde_jsonrc::from_str::<Scripts>(data)
+        .map_err(|err| ScriptError::from_json(err, file))?;
+    scripts.locations = scan_locations(data, file);
+    Ok(scripts)
+}
+
+/// Parses a scripts file in whichever format its extension says, JSON unless
+/// it's a screenplay.
+pub fn parse_scripts_file(data: &str, file: &Path) -> Result<Scripts, ScriptError> {
+    if file.extension().is_some_and(|extension| extension == SCREENPLAY_EXTENSION) {
+        parse_screenplay(data, file)
+    } else {
+        parse_scripts(data, file)
+    }
+}
+
+pub fn load_from_file<P: AsRef<Path>>(file_path: P) -> Result<Scripts, ScriptError> {
+    let file_path = file_path.as_ref();
+    let data = fs::read_to_string(file_path).map_err(|err| ScriptError::Io {
+        file: file_path.to_path_buf(),
+        message: err.to_string(),
+    })?;
+    parse_scripts_file(&data, file_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(data: &str) -> (String, usize, usize) {
+        match parse_scripts(data, Path::new("test.dialogue")) {
+            Err(ScriptError::Parse { location, message }) => (message, location.line, location.column),
+            other => panic!("expected a parse error, got {:?}", other),
         }
-    };
-}
\ No newline at end of file
+    }
+
+    #[test]
+    fn misspelled_key() {
+        let (message, line, _) = error("{\"Start\": [\n  {\"charcter\": \"A\", \"text\": \"Hi.\"}\n]}");
+        assert_eq!(message, "unknown key `charcter`, did you mean `character`?");
+        assert_eq!(line, 2);
+        let (message, _, _) = error("{\"Start\": [{\"character\": \"A\", \"text\": \"Hi.\", \"mood\": 1}]}");
+        assert_eq!(message, "unknown key `mood`");
+    }
+
+    #[test]
+    fn two_kinds() {
+        let (message, _, _) = error("{\"Start\": [{\"cue\": \"a\", \"end\": \"b\"}]}");
+        assert_eq!(message, "item can only be one kind but has keys cue, end");
+        let (message, _, _) = error("{\"Start\": [{\"note\": \"nothing else\"}]}");
+        assert!(message.starts_with("item has none of the keys that say what it is"), "{}", message);
+        // `jump` next to `if` is where the branch goes, not a second kind.
+        let scripts = parse_scripts(
+            "{\"Start\": [{\"if\": \"true\", \"jump\": \"Start\"}]}",
+            Path::new("test.dialogue"),
+        )
+        .unwrap();
+        assert!(matches!(scripts.get_item("Start", 0), Some(ScriptItem::If(_))));
+    }
+
+    #[test]
+    fn unknown_option_key() {
+        let (message, line, _) = error(
+            "{\"Start\": [\n  {\"choice\": [\n    {\"text\": \"Go\", \"jump\": \"Start\", \"conditon\": \"true\"}\n  ]}\n]}",
+        );
+        assert!(message.starts_with("`choice` item: unknown field `conditon`"), "{}", message);
+        assert_eq!(line, 4);
+    }
+
+    #[test]
+    fn notes_round_trip() {
+        let data = r#"{"Start": [
+            {"character": "A", "text": "Hi.", "note": "said quietly"},
+            {"choice": [{"text": "Go", "jump": "Start", "note": "loops"}], "note": "the only way"},
+            {"end": "", "note": "done"}
+        ]}"#;
+        let scripts = parse_scripts(data, Path::new("test.dialogue")).unwrap();
+        let items = scripts.get("Start").unwrap();
+        let notes: Vec<Option<&str>> = items.iter().map(ScriptItem::note).collect();
+        assert_eq!(notes, [Some("said quietly"), Some("the only way"), Some("done")]);
+        let ScriptItem::Choice(choice) = &items[1] else {
+            panic!("expected a choice");
+        };
+        assert_eq!(choice.choice[0].note.as_deref(), Some("loops"));
+
+        let written = serde_jsonrc::to_string(&scripts).unwrap();
+        assert_eq!(parse_scripts(&written, Path::new("test.dialogue")).unwrap(), scripts);
+        assert!(written.contains("\"note\":\"loops\""));
+    }
+}
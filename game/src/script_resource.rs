@@ -12,19 +12,26 @@ use fyrox::{
         loader::{BoxedLoaderFuture, ResourceLoader},
         untyped::UntypedResource, ResourceData,
     }, core::{reflect::Reflect, TypeUuidProvider, uuid::{Uuid, uuid}, visitor::{Visit, VisitResult, Visitor}, reflect::prelude::*, io,},
+    gui::ttf::{Font, SharedFont},
 };
 
-use crate::script::Scripts;
+use crate::{
+    localization::{StringTable, CSV_EXTENSION, PO_EXTENSION},
+    manifest::{Manifest, MANIFEST_EXTENSION},
+    screenplay::SCREENPLAY_EXTENSION,
+    script::{parse_scripts_file, Scripts},
+    theme::{FONT_EXTENSION, FONT_HEIGHT},
+};
+
+/// Scripts files use their own extension, so the loader doesn't grab every
+/// other JSON file the game has.
+pub const SCRIPT_EXTENSION: &str = "dialogue";
 
-#[derive(Debug, Visit, Reflect)]
+#[derive(Debug, Default, Visit, Reflect)]
 pub struct ScriptResource {
     // You resource must store the path.
     path: PathBuf,
-    pub some_data: Scripts,
-}
-impl ScriptResource {
-    pub fn test(){
-    }
+    pub scripts: Scripts,
 }
 
 impl TypeUuidProvider for ScriptResource {
@@ -64,7 +71,7 @@ impl ResourceLoader for ScriptResourceLoader {
         // An array of extensitions, supported by this loader. There could be any number of extensions
         // since sometimes multiple extensions map to a single resource (for instance, jpg, png, bmp, are
         // all images).
-        &["json"]
+        &[SCRIPT_EXTENSION, SCREENPLAY_EXTENSION]
     }
 
     fn into_any(self: Box<Self>) -> Box<dyn Any> {
@@ -89,12 +96,18 @@ impl ResourceLoader for ScriptResourceLoader {
             let path = resource.path();
             match io::load_file(&path).await {
                 Ok(content) => {
-                    let data = String::from_utf8(content).unwrap();
-                    match serde_jsonrc::from_str::<Scripts>(&data) {
-                        Ok(value)=> {
+                    let data = match String::from_utf8(content) {
+                        Ok(data) => data,
+                        Err(err) => {
+                            resource.commit_error(path, err);
+                            return;
+                        }
+                    };
+                    match parse_scripts_file(&data, &path) {
+                        Ok(scripts)=> {
                             let my_resource = ScriptResource {
                                 path,
-                                some_data: value
+                                scripts
                             };
                             resource.commit_ok(my_resource);
 
@@ -111,4 +124,262 @@ impl ResourceLoader for ScriptResourceLoader {
             }
         })
     }
-}
\ No newline at end of file
+}
+/// A loaded manifest, with its patterns already turned into file paths.
+#[derive(Debug, Default, Visit, Reflect)]
+pub struct ManifestResource {
+    path: PathBuf,
+    pub files: Vec<PathBuf>,
+}
+
+impl TypeUuidProvider for ManifestResource {
+    fn type_uuid() -> Uuid {
+        uuid!("3d0c5b6e-8f0a-4b9e-a4a4-2f6f0f6d9c11")
+    }
+}
+
+impl ResourceData for ManifestResource {
+    fn path(&self) -> Cow<Path> {
+        Cow::Borrowed(&self.path)
+    }
+
+    fn set_path(&mut self, path: PathBuf) {
+        self.path = path;
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn type_uuid(&self) -> Uuid {
+        <Self as TypeUuidProvider>::type_uuid()
+    }
+}
+
+pub struct ManifestResourceLoader;
+
+impl ResourceLoader for ManifestResourceLoader {
+    fn extensions(&self) -> &[&str] {
+        &[MANIFEST_EXTENSION]
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn load(
+        &self,
+        resource: UntypedResource,
+        event_broadcaster: ResourceEventBroadcaster,
+        reload: bool,
+    ) -> BoxedLoaderFuture {
+        Box::pin(async move {
+            let path = resource.path();
+            let content = match io::load_file(&path).await {
+                Ok(content) => content,
+                Err(err) => {
+                    resource.commit_error(path, err);
+                    return;
+                }
+            };
+            let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            let files = String::from_utf8(content)
+                .map_err(|err| err.to_string())
+                .and_then(|data| Manifest::parse(&data))
+                .and_then(|manifest| manifest.files(&dir));
+            match files {
+                Ok(files) => {
+                    resource.commit_ok(ManifestResource { path, files });
+                    event_broadcaster.broadcast_loaded_or_reloaded(resource, reload);
+                }
+                Err(err) => {
+                    resource.commit_error(path, err);
+                }
+            }
+        })
+    }
+}
+
+/// One locale's string table.
+#[derive(Debug, Default, Visit, Reflect)]
+pub struct StringTableResource {
+    path: PathBuf,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub table: StringTable,
+}
+
+impl TypeUuidProvider for StringTableResource {
+    fn type_uuid() -> Uuid {
+        uuid!("b6f3e2a1-5c47-4d8e-9a1f-7e2c4b9d0a35")
+    }
+}
+
+impl ResourceData for StringTableResource {
+    fn path(&self) -> Cow<Path> {
+        Cow::Borrowed(&self.path)
+    }
+
+    fn set_path(&mut self, path: PathBuf) {
+        self.path = path;
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn type_uuid(&self) -> Uuid {
+        <Self as TypeUuidProvider>::type_uuid()
+    }
+}
+
+pub struct StringTableResourceLoader;
+
+impl ResourceLoader for StringTableResourceLoader {
+    fn extensions(&self) -> &[&str] {
+        &[PO_EXTENSION, CSV_EXTENSION]
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn load(
+        &self,
+        resource: UntypedResource,
+        event_broadcaster: ResourceEventBroadcaster,
+        reload: bool,
+    ) -> BoxedLoaderFuture {
+        Box::pin(async move {
+            let path = resource.path();
+            let content = match io::load_file(&path).await {
+                Ok(content) => content,
+                Err(err) => {
+                    resource.commit_error(path, err);
+                    return;
+                }
+            };
+            let table = String::from_utf8(content)
+                .map_err(|err| err.to_string())
+                .and_then(|data| StringTable::parse(&data, &path));
+            match table {
+                Ok(table) => {
+                    resource.commit_ok(StringTableResource { path, table });
+                    event_broadcaster.broadcast_loaded_or_reloaded(resource, reload);
+                }
+                Err(err) => {
+                    resource.commit_error(path.clone(), format!("{}: {}", path.display(), err));
+                }
+            }
+        })
+    }
+}
+
+/// A TrueType font, rasterised at `FONT_HEIGHT`.
+#[derive(Debug, Default, Visit, Reflect)]
+pub struct FontResource {
+    path: PathBuf,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub font: Option<SharedFont>,
+}
+
+impl TypeUuidProvider for FontResource {
+    fn type_uuid() -> Uuid {
+        uuid!("3d8c1f52-9e6a-4b07-a4d3-6f1e2b8c5a90")
+    }
+}
+
+impl ResourceData for FontResource {
+    fn path(&self) -> Cow<Path> {
+        Cow::Borrowed(&self.path)
+    }
+
+    fn set_path(&mut self, path: PathBuf) {
+        self.path = path;
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn type_uuid(&self) -> Uuid {
+        <Self as TypeUuidProvider>::type_uuid()
+    }
+}
+
+pub struct FontResourceLoader;
+
+impl ResourceLoader for FontResourceLoader {
+    fn extensions(&self) -> &[&str] {
+        &[FONT_EXTENSION]
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn load(
+        &self,
+        resource: UntypedResource,
+        event_broadcaster: ResourceEventBroadcaster,
+        reload: bool,
+    ) -> BoxedLoaderFuture {
+        Box::pin(async move {
+            let path = resource.path();
+            let content = match io::load_file(&path).await {
+                Ok(content) => content,
+                Err(err) => {
+                    resource.commit_error(path, err);
+                    return;
+                }
+            };
+            match Font::from_memory(content, FONT_HEIGHT, Font::default_char_set()) {
+                Ok(font) => {
+                    let font = Some(SharedFont::new(font));
+                    resource.commit_ok(FontResource { path, font });
+                    event_broadcaster.broadcast_loaded_or_reloaded(resource, reload);
+                }
+                Err(_) => {
+                    resource.commit_error(path.clone(), format!("{} isn't a font", path.display()));
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,155 @@
+//! Lines the player has read, in this or any earlier playthrough.
+//!
+//! Unlike saves there's only one of these, it's kept next to the saves and
+//! written whenever a new line is read. Skip mode uses it to fast-forward
+//! through what the player already knows.
+use std::collections::{BTreeMap, BTreeSet};
+
+use fyrox::core::log::Log;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    runner::Line,
+    save::{read_file, write_file, SaveError},
+    ScriptPos,
+};
+
+/// Not `.json`, so it isn't listed as a save slot.
+pub const SEEN_FILE: &str = "seen.dat";
+
+/// Blurps by `(script, index)`, editing a script can make a line count as
+/// read when it isn't or the other way round.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SeenLines {
+    lines: BTreeMap<String, BTreeSet<usize>>,
+    /// Bumped on every change so the game can tell it needs writing.
+    #[serde(skip)]
+    revision: u64,
+}
+
+impl SeenLines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn contains(&self, pos: &ScriptPos) -> bool {
+        self.lines
+            .get(&pos.script)
+            .is_some_and(|indices| indices.contains(&pos.index))
+    }
+    /// Returns false if the line was seen already.
+    pub fn insert(&mut self, pos: &ScriptPos) -> bool {
+        let inserted = self
+            .lines
+            .entry(pos.script.clone())
+            .or_default()
+            .insert(pos.index);
+        if inserted {
+            self.revision += 1;
+        }
+        inserted
+    }
+    pub fn len(&self) -> usize {
+        self.lines.values().map(BTreeSet::len).sum()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+}
+
+/// What skip mode fast-forwards through. It always stops at choices, topic
+/// hubs and prompts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipMode {
+    Off,
+    /// Only lines that were read before, stops at the first new one.
+    Seen,
+    All,
+}
+
+impl SkipMode {
+    /// The mode the skip key switches to.
+    pub fn next(self) -> Self {
+        match self {
+            SkipMode::Off => SkipMode::Seen,
+            SkipMode::Seen => SkipMode::All,
+            SkipMode::All => SkipMode::Off,
+        }
+    }
+    pub fn skips(self, line: &Line) -> bool {
+        match self {
+            SkipMode::Off => false,
+            SkipMode::Seen => line.seen,
+            SkipMode::All => true,
+        }
+    }
+}
+
+/// Nothing is seen when the file doesn't exist yet or is unreadable.
+pub fn read_seen() -> SeenLines {
+    let data = match read_file(SEEN_FILE) {
+        Ok(data) => data,
+        Err(err) => {
+            Log::info(format!("no seen lines: {}", err));
+            return SeenLines::new();
+        }
+    };
+    match serde_jsonrc::from_str(&data) {
+        Ok(seen) => seen,
+        Err(err) => {
+            Log::warn(format!("{} is corrupt, starting over: {}", SEEN_FILE, err));
+            SeenLines::new()
+        }
+    }
+}
+
+pub fn write_seen(seen: &SeenLines) -> Result<(), SaveError> {
+    let data = serde_jsonrc::to_string(seen).map_err(|err| SaveError::Parse(err.to_string()))?;
+    write_file(SEEN_FILE, &data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(script: &str, index: usize) -> ScriptPos {
+        ScriptPos {
+            script: script.to_string(),
+            index,
+        }
+    }
+
+    #[test]
+    fn insert() {
+        let mut seen = SeenLines::new();
+        assert!(seen.is_empty());
+        assert!(seen.insert(&pos("Start", 0)));
+        assert!(seen.insert(&pos("Start", 2)));
+        assert!(seen.insert(&pos("Other", 0)));
+        assert!(!seen.insert(&pos("Start", 2)));
+        assert_eq!(seen.len(), 3);
+        assert_eq!(seen.revision(), 3);
+        assert!(seen.contains(&pos("Start", 2)));
+        assert!(!seen.contains(&pos("Start", 1)));
+        assert!(!seen.contains(&pos("Missing", 0)));
+    }
+
+    // Same conversion `write_seen` and `read_seen` do, without touching the
+    // real file.
+    #[test]
+    fn reload() {
+        let mut seen = SeenLines::new();
+        seen.insert(&pos("Start", 0));
+        seen.insert(&pos("Start", 3));
+        let data = serde_jsonrc::to_string(&seen).unwrap();
+        let loaded: SeenLines = serde_jsonrc::from_str(&data).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert!(loaded.contains(&pos("Start", 0)));
+        assert!(loaded.contains(&pos("Start", 3)));
+        assert!(!loaded.contains(&pos("Start", 1)));
+        // A freshly read file doesn't need writing again.
+        assert_eq!(loaded.revision(), 0);
+    }
+}
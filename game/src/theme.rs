@@ -0,0 +1,170 @@
+//! How the game's text looks, in one place instead of at every builder.
+use fyrox::{
+    asset::{manager::ResourceManager, Resource},
+    gui::{
+        brush::Brush,
+        core::{algebra::Vector2, color::Color},
+        ttf::SharedFont,
+        widget::WidgetBuilder,
+    },
+};
+
+use crate::{better_text::TextBuilder, script_resource::FontResource};
+
+pub const FONT_EXTENSION: &str = "ttf";
+/// Pixel height fonts are rasterised at, text is scaled from it.
+pub const FONT_HEIGHT: f32 = 20.0;
+
+/// Which of the theme's fonts a file is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FontSlot {
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+}
+
+/// The theme's fonts, DejaVu Sans, see `data/fonts/LICENSE`. Until one has
+/// loaded, or if it fails to, the UI's default font is used and `<b>` and
+/// `<i>` are faked from the regular font.
+pub const FONTS: [(FontSlot, &str); 4] = [
+    (FontSlot::Regular, "data/fonts/DejaVuSans.ttf"),
+    (FontSlot::Bold, "data/fonts/DejaVuSans-Bold.ttf"),
+    (FontSlot::Italic, "data/fonts/DejaVuSans-Oblique.ttf"),
+    (FontSlot::BoldItalic, "data/fonts/DejaVuSans-BoldOblique.ttf"),
+];
+
+/// The look of one kind of text.
+#[derive(Debug, Clone)]
+pub struct TextTheme {
+    /// In pixels, the font's own height when `None`.
+    pub font_size: Option<f32>,
+    pub foreground: Color,
+    /// Drawn under `<em>` text that has no colour of its own.
+    pub emphasis: Color,
+    pub shadow: Option<ShadowTheme>,
+    pub outline: Option<OutlineTheme>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ShadowTheme {
+    pub color: Color,
+    pub offset: Vector2<f32>,
+    pub dilation: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct OutlineTheme {
+    pub color: Color,
+    /// In pixels.
+    pub thickness: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// The UI's default font when `None`.
+    pub font: Option<SharedFont>,
+    /// For `<b>`, `<i>` and both, faked from `font` when `None`.
+    pub bold_font: Option<SharedFont>,
+    pub italic_font: Option<SharedFont>,
+    pub bold_italic_font: Option<SharedFont>,
+    pub dialogue: TextTheme,
+    pub name_plate: TextTheme,
+    pub choice: TextTheme,
+    /// Foreground of choices that can't be picked.
+    pub disabled_choice: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let shadow = ShadowTheme {
+            color: Color::BLACK,
+            offset: Vector2::new(1.0, 1.0),
+            dilation: 1.0,
+        };
+        Self {
+            font: None,
+            bold_font: None,
+            italic_font: None,
+            bold_italic_font: None,
+            dialogue: TextTheme {
+                font_size: None,
+                foreground: Color::opaque(220, 220, 220),
+                emphasis: Color::opaque(255, 200, 120),
+                shadow: Some(shadow.clone()),
+                outline: None,
+            },
+            name_plate: TextTheme {
+                font_size: None,
+                foreground: Color::opaque(255, 220, 120),
+                emphasis: Color::opaque(255, 255, 255),
+                shadow: None,
+                outline: Some(OutlineTheme {
+                    color: Color::opaque(40, 30, 20),
+                    thickness: 1.0,
+                }),
+            },
+            choice: TextTheme {
+                font_size: None,
+                foreground: Color::opaque(220, 220, 220),
+                emphasis: Color::opaque(255, 200, 120),
+                shadow: Some(shadow),
+                outline: None,
+            },
+            disabled_choice: Color::opaque(120, 120, 120),
+        }
+    }
+}
+
+impl Theme {
+    /// Starts loading `FONTS`, hand each to `set_font` once it has.
+    pub fn request_fonts(resource_manager: &ResourceManager) -> Vec<(FontSlot, Resource<FontResource>)> {
+        FONTS
+            .iter()
+            .map(|(slot, path)| (*slot, resource_manager.request::<FontResource, _>(path)))
+            .collect()
+    }
+
+    pub fn set_font(&mut self, slot: FontSlot, font: SharedFont) {
+        let field = match slot {
+            FontSlot::Regular => &mut self.font,
+            FontSlot::Bold => &mut self.bold_font,
+            FontSlot::Italic => &mut self.italic_font,
+            FontSlot::BoldItalic => &mut self.bold_italic_font,
+        };
+        *field = Some(font);
+    }
+
+    /// A text builder for `text`, with everything but the content set.
+    pub fn text_builder(&self, widget_builder: WidgetBuilder, text: &TextTheme) -> TextBuilder {
+        let mut builder = TextBuilder::new(widget_builder.with_foreground(Brush::Solid(text.foreground)))
+            .with_opt_font(self.font.clone())
+            .with_emphasis_brush(Brush::Solid(text.emphasis));
+        if let Some(font) = &self.bold_font {
+            builder = builder.with_bold_font(font.clone());
+        }
+        if let Some(font) = &self.italic_font {
+            builder = builder.with_italic_font(font.clone());
+        }
+        if let Some(font) = &self.bold_italic_font {
+            builder = builder.with_bold_italic_font(font.clone());
+        }
+        if let Some(size) = text.font_size {
+            builder = builder.with_font_size(size);
+        }
+        if let Some(shadow) = &text.shadow {
+            builder = builder
+                .with_shadow(true)
+                .with_shadow_brush(Brush::Solid(shadow.color))
+                .with_shadow_offset(shadow.offset)
+                .with_shadow_dilation(shadow.dilation);
+        }
+        if let Some(outline) = &text.outline {
+            builder = builder
+                .with_outline(true)
+                .with_outline_brush(Brush::Solid(outline.color))
+                .with_outline_thickness(outline.thickness);
+        }
+        builder
+    }
+}
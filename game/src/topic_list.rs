@@ -1,37 +1,97 @@
 use std::collections::{BTreeSet, HashMap};
 
-pub struct TopicList (HashMap::<String, BTreeSet::<String>>);
+use serde::{Deserialize, Serialize};
+
+/// Gates a topic item on a script expression.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ItemCondition {
+    pub condition: String,
+    /// Grey the item out instead of hiding it while `condition` is false.
+    pub show_disabled: bool,
+}
+
+/// Topic → items offered by an `offer_topics` hub. Every item names the
+/// script that runs when it's picked.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TopicList {
+    topics: HashMap::<String, BTreeSet::<String>>,
+    /// Topic → item → condition, for the items that have one.
+    #[serde(default)]
+    conditions: HashMap::<String, HashMap::<String, ItemCondition>>,
+    /// Bumped on every change so open menus can tell they're stale.
+    #[serde(skip)]
+    revision: u64,
+}
 impl TopicList {
     pub fn get(&self, key: &str) -> Option<&BTreeSet<String>> {
-        self.0.get(key)
+        self.topics.get(key)
+    }
+    pub fn contains(&self, topic: &str, item: &str) -> bool {
+        self.topics.get(topic).is_some_and(|items| items.contains(item))
+    }
+    pub fn condition(&self, topic: &str, item: &str) -> Option<&ItemCondition> {
+        self.conditions.get(topic)?.get(item)
+    }
+    /// Like `insert_item_in_topic`, but replaces the item's condition.
+    pub fn insert_item_with_condition(&mut self, topic: &str, item: String, condition: Option<ItemCondition>) {
+        match condition {
+            Some(condition) => {
+                self.conditions
+                    .entry(topic.to_string())
+                    .or_default()
+                    .insert(item.clone(), condition);
+            }
+            None => {
+                if let Some(conditions) = self.conditions.get_mut(topic) {
+                    conditions.remove(&item);
+                }
+            }
+        }
+        self.insert_item_in_topic(topic, item);
+    }
+    pub fn revision(&self) -> u64 {
+        self.revision
     }
     pub fn insert_item_in_topic(&mut self, key:&str, value: String) {
-
-        if let Some(topic) = self.0.get_mut(key) {
+        self.revision += 1;
+        if let Some(topic) = self.topics.get_mut(key) {
             topic.insert(value);
             return;
-            
+
         } else {
             let mut topic = BTreeSet::new();
             topic.insert(value);
-            self.0.insert(key.to_string(), topic);
+            self.topics.insert(key.to_string(), topic);
             return;
         };
     }
     pub fn add_topic(&mut self, topic: String, items: BTreeSet::<String>) -> Option<BTreeSet::<String>>{
-        self.0.insert(topic, items)
+        self.revision += 1;
+        self.topics.insert(topic, items)
     }
     pub fn remove_item(&mut self, topic: &str, item: &str) -> bool {
-        let Some(thing) = self.0.get_mut(topic) else {
+        let Some(thing) = self.topics.get_mut(topic) else {
             return false;
         };
-        thing.remove(item)
+        let removed = thing.remove(item);
+        if removed {
+            if let Some(conditions) = self.conditions.get_mut(topic) {
+                conditions.remove(item);
+            }
+            self.revision += 1;
+        }
+        removed
     }
     pub fn remove_topic(&mut self, topic: &str) -> Option<BTreeSet<String>> {
-        self.0.remove(topic)
+        let removed = self.topics.remove(topic);
+        self.conditions.remove(topic);
+        if removed.is_some() {
+            self.revision += 1;
+        }
+        removed
     }
     pub fn new()->Self {
-        Self(HashMap::new())
+        Self { topics: HashMap::new(), conditions: HashMap::new(), revision: 0 }
     }
 
-}
\ No newline at end of file
+}
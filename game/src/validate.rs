@@ -0,0 +1,418 @@
+//! Static checks over `Scripts`, so broken scripts are caught before anyone
+//! plays through them. Used by the `check-scripts` binary.
+use std::{
+    collections::{BTreeSet, HashSet, VecDeque},
+    fmt,
+    path::Path,
+};
+
+use crate::{
+    action::ActionRegistry,
+    expr::{parse_assignment, parse_expr, Expr},
+    location::{scan_locations, Location, ScriptLocations},
+    markup::parse_markup,
+    placeholder::{check_placeholders, known_variables},
+    script::{closest_key, ScriptItem, Scripts, ITEM_KEYS, OPTION_KEYS},
+    START_SCRIPT,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub severity: Severity,
+    pub script: String,
+    /// Item the issue is about, `None` when it's about the whole script.
+    pub index: Option<usize>,
+    pub message: String,
+    /// Where the script or item is written, when known.
+    pub location: Option<Location>,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        if let Some(location) = &self.location {
+            write!(f, "{}: ", location)?;
+        }
+        match self.index {
+            Some(index) => write!(f, "{}: {} @{}: {}", severity, self.script, index, self.message),
+            None => write!(f, "{}: {}: {}", severity, self.script, self.message),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ValidationOptions {
+    /// Scripts the game starts on its own, everything else has to be
+    /// reachable from one of them.
+    pub entry_points: Vec<String>,
+    /// Characters blurps may use. Without a list, characters that are only
+    /// used once are reported as likely typos instead.
+    pub characters: Option<BTreeSet<String>>,
+}
+
+impl Default for ValidationOptions {
+    fn default() -> Self {
+        Self {
+            entry_points: vec![START_SCRIPT.to_string()],
+            characters: None,
+        }
+    }
+}
+
+fn issue(severity: Severity, script: &str, index: Option<usize>, message: String) -> Issue {
+    Issue {
+        severity,
+        script: script.to_string(),
+        index,
+        message,
+        location: None,
+    }
+}
+
+/// Fills in `Issue::location` from where the scripts and items start.
+fn locate(issues: &mut [Issue], locations: impl Fn(&str) -> Option<ScriptLocations>) {
+    for issue in issues {
+        let Some(found) = locations(&issue.script) else {
+            continue;
+        };
+        issue.location = match issue.index {
+            Some(index) => found.items.get(index).cloned(),
+            None => Some(found.script),
+        };
+    }
+}
+
+/// Scripts an item can send the runner to.
+fn targets(item: &ScriptItem) -> Vec<&str> {
+    match item {
+        ScriptItem::AddQuest(quest) => vec![&quest.add_quest_script],
+        ScriptItem::AddTopicItem(add) => vec![&add.item],
+        ScriptItem::Choice(choice) => choice.choice.iter().map(|option| option.jump.as_str()).collect(),
+        ScriptItem::If(branch) => std::iter::once(branch.jump.as_str())
+            .chain(branch.else_jump.as_deref())
+            .collect(),
+        ScriptItem::Jump(jump) => vec![&jump.jump],
+        _ => Vec::new(),
+    }
+}
+
+/// Whether the runner can never get past `item` to the one after it.
+pub(crate) fn is_terminator(item: &ScriptItem) -> bool {
+    match item {
+        ScriptItem::End(_) | ScriptItem::Jump(_) => true,
+        ScriptItem::Choice(choice) => !choice.choice.is_empty(),
+        ScriptItem::If(branch) => branch.else_jump.is_some(),
+        ScriptItem::OfferTopics(offer) => !offer.exit,
+        _ => false,
+    }
+}
+
+pub fn validate(scripts: &Scripts, options: &ValidationOptions) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let actions = ActionRegistry::with_builtin_actions();
+    let mut names: Vec<&String> = scripts.keys().collect();
+    names.sort();
+
+    for entry in &options.entry_points {
+        if !scripts.has_script(entry) {
+            issues.push(issue(Severity::Error, entry, None, "entry point doesn't exist".to_string()));
+        }
+    }
+
+    let known = known_variables(scripts);
+    let mut character_uses = std::collections::BTreeMap::<&str, Vec<(&str, usize)>>::new();
+    for name in &names {
+        let items = scripts.get(name).map(Vec::as_slice).unwrap_or_default();
+        if items.is_empty() {
+            issues.push(issue(Severity::Warning, name, None, "script is empty".to_string()));
+        }
+        for (index, item) in items.iter().enumerate() {
+            let at = Some(index);
+            for target in targets(item) {
+                if !scripts.has_script(target) {
+                    issues.push(issue(
+                        Severity::Error,
+                        name,
+                        at,
+                        format!("refers to non existant script {}", target),
+                    ));
+                }
+            }
+            match item {
+                ScriptItem::Action(action) if !actions.contains(&action.action) => {
+                    issues.push(issue(
+                        Severity::Error,
+                        name,
+                        at,
+                        format!("unknown action {:?}", action.action),
+                    ));
+                }
+                ScriptItem::Blurp(blurp) => {
+                    character_uses
+                        .entry(blurp.character.as_str())
+                        .or_default()
+                        .push((name.as_str(), index));
+                    for problem in parse_markup(&blurp.text).problems {
+                        issues.push(issue(Severity::Warning, name, at, problem));
+                    }
+                }
+                ScriptItem::Choice(choice) => {
+                    if choice.choice.is_empty() {
+                        issues.push(issue(Severity::Error, name, at, "choice has no options".to_string()));
+                    }
+                    for (option_index, option) in choice.choice.iter().enumerate() {
+                        if let Some(condition) = &option.condition {
+                            match parse_expr(condition) {
+                                Ok(expr) => unset_variables(&expr, &known, name, index, &mut issues),
+                                Err(err) => issues.push(issue(
+                                    Severity::Error,
+                                    name,
+                                    at,
+                                    format!("option {} has a bad condition {:?}: {}", option_index, condition, err),
+                                )),
+                            }
+                        }
+                    }
+                }
+                ScriptItem::AddTopicItem(add) => {
+                    if let Some(condition) = &add.condition {
+                        match parse_expr(condition) {
+                            Ok(expr) => unset_variables(&expr, &known, name, index, &mut issues),
+                            Err(err) => issues.push(issue(
+                                Severity::Error,
+                                name,
+                                at,
+                                format!("bad condition {:?}: {}", condition, err),
+                            )),
+                        }
+                    }
+                }
+                ScriptItem::If(branch) => {
+                    match parse_expr(&branch.condition) {
+                        Ok(expr) => unset_variables(&expr, &known, name, index, &mut issues),
+                        Err(err) => issues.push(issue(
+                            Severity::Error,
+                            name,
+                            at,
+                            format!("bad condition {:?}: {}", branch.condition, err),
+                        )),
+                    }
+                }
+                ScriptItem::Set(set) => {
+                    match parse_assignment(&set.set) {
+                        Ok(assignment) => unset_variables(&assignment.expr, &known, name, index, &mut issues),
+                        Err(err) => issues.push(issue(
+                            Severity::Error,
+                            name,
+                            at,
+                            format!("bad set {:?}: {}", set.set, err),
+                        )),
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(last) = items.last() {
+            if !is_terminator(last) {
+                issues.push(issue(
+                    Severity::Warning,
+                    name,
+                    Some(items.len() - 1),
+                    "script can fall off the end without an end or jump".to_string(),
+                ));
+            }
+        }
+    }
+
+    match &options.characters {
+        Some(characters) => {
+            for (character, uses) in &character_uses {
+                if !characters.contains(*character) {
+                    for (script, index) in uses {
+                        issues.push(issue(
+                            Severity::Error,
+                            script,
+                            Some(*index),
+                            format!("unknown character {:?}", character),
+                        ));
+                    }
+                }
+            }
+        }
+        None => {
+            // Narration has no character, so there's nothing to misspell.
+            for (character, uses) in character_uses.iter().filter(|(character, _)| !character.is_empty()) {
+                if let [(script, index)] = uses.as_slice() {
+                    issues.push(issue(
+                        Severity::Warning,
+                        script,
+                        Some(*index),
+                        format!("character {:?} is only used once, is it misspelled?", character),
+                    ));
+                }
+            }
+        }
+    }
+
+    for reachable_issue in unreachable(scripts, &options.entry_points, &names) {
+        issues.push(reachable_issue);
+    }
+
+    for placeholder in check_placeholders(scripts) {
+        issues.push(issue(
+            Severity::Error,
+            &placeholder.script,
+            Some(placeholder.index),
+            placeholder.message,
+        ));
+    }
+
+    locate(&mut issues, |script| scripts.locations(script).cloned());
+    issues.sort_by(|a, b| (&a.script, a.index).cmp(&(&b.script, b.index)));
+    issues
+}
+
+/// Variables `expr` reads that no `set` assigns, it would fail every time
+/// it's evaluated.
+fn unset_variables(expr: &Expr, known: &BTreeSet<String>, script: &str, index: usize, issues: &mut Vec<Issue>) {
+    let mut reported = BTreeSet::new();
+    for variable in expr.variables() {
+        if !known.contains(variable) && reported.insert(variable) {
+            issues.push(issue(
+                Severity::Error,
+                script,
+                Some(index),
+                format!("reads {} which is never set", variable),
+            ));
+        }
+    }
+}
+
+fn unreachable(scripts: &Scripts, entry_points: &[String], names: &[&String]) -> Vec<Issue> {
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut queue: VecDeque<&str> = entry_points
+        .iter()
+        .map(String::as_str)
+        .filter(|entry| scripts.has_script(entry))
+        .collect();
+    while let Some(name) = queue.pop_front() {
+        if !seen.insert(name) {
+            continue;
+        }
+        for item in scripts.get(name).into_iter().flatten() {
+            for target in targets(item) {
+                if scripts.has_script(target) && !seen.contains(target) {
+                    queue.push_back(target);
+                }
+            }
+        }
+    }
+    names
+        .iter()
+        .filter(|name| !seen.contains(name.as_str()))
+        .map(|name| {
+            issue(
+                Severity::Warning,
+                name,
+                None,
+                "script can't be reached from any entry point".to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Checks the raw JSON of a scripts file for unknown keys. Loading stops at
+/// the first one, this reports all of them. Keys close to a known one are
+/// reported as misspellings.
+pub fn validate_keys(data: &str, file: &Path) -> Result<Vec<Issue>, String> {
+    let value: serde_jsonrc::Value = serde_jsonrc::from_str(data).map_err(|err| err.to_string())?;
+    let Some(scripts) = value.as_object() else {
+        return Err("scripts file should be an object of scripts".to_string());
+    };
+    let mut issues = Vec::new();
+    for (name, items) in scripts {
+        let Some(items) = items.as_array() else {
+            issues.push(issue(Severity::Error, name, None, "script should be an array of items".to_string()));
+            continue;
+        };
+        for (index, item) in items.iter().enumerate() {
+            let Some(item) = item.as_object() else {
+                issues.push(issue(Severity::Error, name, Some(index), "item should be an object".to_string()));
+                continue;
+            };
+            for key in item.keys() {
+                check_key(key, ITEM_KEYS, name, index, "", &mut issues);
+            }
+            if let Some(options) = item.get("choice").and_then(|choice| choice.as_array()) {
+                for (option_index, option) in options.iter().enumerate() {
+                    for key in option.as_object().into_iter().flat_map(|option| option.keys()) {
+                        let context = format!("option {} ", option_index);
+                        check_key(key, OPTION_KEYS, name, index, &context, &mut issues);
+                    }
+                }
+            }
+        }
+    }
+    let locations = scan_locations(data, file);
+    locate(&mut issues, |script| locations.get(script).cloned());
+    issues.sort_by(|a, b| (&a.script, a.index).cmp(&(&b.script, b.index)));
+    Ok(issues)
+}
+
+fn check_key(key: &str, known: &[&str], script: &str, index: usize, context: &str, issues: &mut Vec<Issue>) {
+    if known.contains(&key) {
+        return;
+    }
+    let found = match closest_key(key, known) {
+        Some(candidate) => issue(
+            Severity::Error,
+            script,
+            Some(index),
+            format!("{}key {:?} is probably a misspelling of {:?}", context, key, candidate),
+        ),
+        None => issue(
+            Severity::Error,
+            script,
+            Some(index),
+            format!("{}unknown key {:?}", context, key),
+        ),
+    };
+    issues.push(found);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::screenplay::parse_screenplay;
+
+    #[test]
+    fn unset_variables_are_reported() {
+        let scripts = parse_screenplay(
+            "== Start ==\n@set visits += 1\n@set title = rank + Name\n@if visits > 1 && seal -> Start\n* Go -> Start if seal || seal\n",
+            Path::new("start.screenplay"),
+        )
+        .unwrap();
+        let messages: Vec<(Option<usize>, String)> = validate(&scripts, &ValidationOptions::default())
+            .into_iter()
+            .filter(|issue| issue.message.contains("never set"))
+            .map(|issue| (issue.index, issue.message))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (Some(1), "reads rank which is never set".to_string()),
+                (Some(2), "reads seal which is never set".to_string()),
+                (Some(3), "reads seal which is never set".to_string()),
+            ]
+        );
+    }
+}
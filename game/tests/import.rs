@@ -0,0 +1,70 @@
+//! Imports the Ink and Yarn fixtures and compares them with the screenplays
+//! they're expected to turn into.
+use std::{fs, path::Path};
+
+use bleeping_fyrox::{
+    import::import_file,
+    screenplay::{export_screenplay, parse_screenplay},
+};
+
+fn fixture(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+    fs::read_to_string(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err))
+}
+
+/// Imports `name`, checks it against `name.screenplay` and that the
+/// screenplay reads back into the same scripts. Returns the issues as
+/// `line: message`.
+fn round_trip(name: &str) -> Vec<String> {
+    let imported = import_file(&fixture(name), Path::new(name)).expect("not an importable file");
+    let screenplay = export_screenplay(&imported.scripts);
+    assert_eq!(screenplay, fixture(&format!("{}.screenplay", name)));
+    let parsed = parse_screenplay(&screenplay, Path::new("imported.screenplay")).unwrap();
+    assert_eq!(parsed, imported.scripts);
+    imported
+        .issues
+        .iter()
+        .map(|issue| {
+            let line = issue.location.as_ref().map_or(0, |location| location.line);
+            format!("{}: {}", line, issue.message)
+        })
+        .collect()
+}
+
+#[test]
+fn ink() {
+    let once = "once-only choices aren't supported, it can be picked again";
+    assert_eq!(
+        round_trip("oasis.ink"),
+        [
+            format!("9: {}", once),
+            format!("10: {}", once),
+            format!("27: {}", once),
+            format!("29: {}", once),
+            "32: function double(x) isn't supported, skipped".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn yarn() {
+    assert_eq!(
+        round_trip("oasis.yarn"),
+        [
+            "25: <<wait 2>> isn't supported",
+            "26: <<if>> blocks aren't supported, skipped",
+        ]
+    );
+}
+
+#[test]
+fn unsupported_ink() {
+    let data = "=== start ===\nHello <> there\n<- thread\n** nested\n-> a ->\n";
+    let imported = import_file(data, Path::new("story.ink")).unwrap();
+    let lines: Vec<usize> = imported
+        .issues
+        .iter()
+        .filter_map(|issue| issue.location.as_ref().map(|location| location.line))
+        .collect();
+    assert_eq!(lines, [2, 3, 4, 5]);
+}